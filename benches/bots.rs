@@ -0,0 +1,51 @@
+//! Plays the beam search bot against the greedy bot on a few seeds, each bot playing both
+//! sides, prints the score and fails unless the beam search bot wins more matches. Run with
+//! `cargo bench --bench bots`.
+
+extern crate tetrs;
+
+use std::time::Instant;
+
+use tetrs::battle::Outcome;
+use tetrs::bot::{self, Bot, Strategy};
+use tetrs::search::Search;
+
+const SEEDS: u64 = 10;
+/// Five minutes of play.
+const MAX_FRAMES: u32 = 5 * 60 * 60;
+const BOT_DELAY: u32 = 1;
+
+fn main() {
+    let mut wins = [0, 0];
+    let mut draws = 0;
+    let start = Instant::now();
+    for seed in 0..SEEDS {
+        let mut beam = Bot::new(BOT_DELAY);
+        beam.strategy = Strategy::Beam(Search::default());
+        let mut greedy = Bot::new(BOT_DELAY);
+        // The beam bot plays the left board on even seeds, the right one on odd seeds.
+        let beam_side = (seed % 2) as usize;
+        let outcome = if beam_side == 0 {
+            bot::duel([&mut beam, &mut greedy], seed, MAX_FRAMES).outcome()
+        } else {
+            bot::duel([&mut greedy, &mut beam], seed, MAX_FRAMES).outcome()
+        };
+        match outcome {
+            Some(Outcome::Winner(winner)) if winner == beam_side => wins[0] += 1,
+            Some(Outcome::Winner(_)) => wins[1] += 1,
+            Some(Outcome::Draw) | None => draws += 1,
+        }
+        println!("seed {}: {:?}", seed, outcome);
+    }
+    println!(
+        "beam {} - greedy {} ({} draws) in {:?}",
+        wins[0],
+        wins[1],
+        draws,
+        start.elapsed()
+    );
+    assert!(
+        wins[0] > wins[1],
+        "the beam search bot should beat the greedy bot"
+    );
+}
@@ -0,0 +1,65 @@
+//! Times the move generator on boards left by the bot after some pieces, the generator being
+//! failing if it takes a millisecond or more per piece. Run with `cargo bench`.
+
+extern crate tetrs;
+
+use std::time::{Duration, Instant};
+
+use tetrs::bot::Bot;
+use tetrs::game::Game;
+use tetrs::movegen;
+use tetrs::tetromino;
+
+const BOARDS: u64 = 20;
+const PIECES_PLAYED: usize = 30;
+const ROUNDS: u32 = 200;
+/// Time a call may take at most, bots generating the placements of every piece they look at.
+const BUDGET: Duration = Duration::from_millis(1);
+
+fn main() {
+    let bot = Bot::new(0);
+    let mut boards = Vec::new();
+    for seed in 0..BOARDS {
+        let mut game = Game::with_seed(seed);
+        for _ in 0..PIECES_PLAYED {
+            let piece = game.create_new_tetromino();
+            if !piece.test_current_position(&game.game_map) {
+                break;
+            }
+            game.current_piece = Some(piece);
+            let placement = match bot.best_placement(&game) {
+                Some(placement) => placement,
+                None => break,
+            };
+            for action in placement.actions {
+                game.apply_action(action);
+            }
+        }
+        boards.push(game.game_map);
+    }
+
+    let pieces: Vec<_> = (1..=7).filter_map(tetromino::from_kind).collect();
+    let mut placements = 0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for board in boards.iter() {
+            for piece in pieces.iter() {
+                placements += movegen::placements(board, piece).len();
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+    let calls = ROUNDS * boards.len() as u32 * pieces.len() as u32;
+    println!(
+        "movegen: {} calls, {:.1} placements per call, {:?} per call",
+        calls,
+        placements as f64 / calls as f64,
+        elapsed / calls
+    );
+    assert!(
+        elapsed / calls < BUDGET,
+        "movegen takes {:?} per call, over its budget of {:?}",
+        elapsed / calls,
+        BUDGET
+    );
+}
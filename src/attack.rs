@@ -0,0 +1,164 @@
+use std::collections::VecDeque;
+
+use crate::game::{Game, HoleRule, LineClear};
+
+/// Frames (at 60 per second) before received garbage can enter the board.
+pub const GARBAGE_DELAY: u32 = 60;
+
+/// Number of garbage lines sent for each kind of line clear.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackTable {
+    pub single: u32,
+    pub double: u32,
+    pub triple: u32,
+    pub tetris: u32,
+    pub t_spin_single: u32,
+    pub t_spin_double: u32,
+    pub t_spin_triple: u32,
+    pub back_to_back: u32,
+    pub all_clear: u32,
+    /// Bonus indexed by the number of clears in a row before this one, the last entry
+    /// being used for longer combos.
+    pub combo: Vec<u32>,
+}
+
+impl Default for AttackTable {
+    fn default() -> AttackTable {
+        AttackTable {
+            single: 0,
+            double: 1,
+            triple: 2,
+            tetris: 4,
+            t_spin_single: 2,
+            t_spin_double: 4,
+            t_spin_triple: 6,
+            back_to_back: 1,
+            all_clear: 10,
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        }
+    }
+}
+
+impl AttackTable {
+    pub fn attack(&self, clear: &LineClear) -> u32 {
+        if clear.lines == 0 {
+            return 0;
+        }
+        let mut lines = match (clear.t_spin, clear.lines) {
+            (true, 1) => self.t_spin_single,
+            (true, 2) => self.t_spin_double,
+            (true, _) => self.t_spin_triple,
+            (false, 1) => self.single,
+            (false, 2) => self.double,
+            (false, 3) => self.triple,
+            (false, _) => self.tetris,
+        };
+        if clear.back_to_back {
+            lines += self.back_to_back;
+        }
+        if clear.all_clear {
+            lines += self.all_clear;
+        }
+        if let Some(last) = self.combo.last() {
+            lines += *self.combo.get(clear.combo as usize).unwrap_or(last);
+        }
+        lines
+    }
+}
+
+/// A batch of garbage received from one attack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Garbage {
+    pub lines: u32,
+    pub frames_left: u32,
+}
+
+/// Garbage waiting to enter a player's board.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GarbageQueue {
+    pub incoming: VecDeque<Garbage>,
+    pub delay: u32,
+}
+
+impl GarbageQueue {
+    pub fn new(delay: u32) -> GarbageQueue {
+        GarbageQueue {
+            incoming: VecDeque::new(),
+            delay,
+        }
+    }
+
+    /// Total number of lines waiting, ready or not.
+    pub fn pending(&self) -> u32 {
+        self.incoming.iter().map(|garbage| garbage.lines).sum()
+    }
+
+    pub fn receive(&mut self, lines: u32) {
+        if lines > 0 {
+            self.incoming.push_back(Garbage {
+                lines,
+                frames_left: self.delay,
+            });
+        }
+    }
+
+    /// Advances the garbage delay by one frame.
+    pub fn tick(&mut self) {
+        for garbage in self.incoming.iter_mut() {
+            garbage.frames_left = garbage.frames_left.saturating_sub(1);
+        }
+    }
+
+    /// Uses `lines` of attack to cancel the oldest incoming garbage first. Returns what is left
+    /// to send to the opponent.
+    pub fn cancel(&mut self, mut lines: u32) -> u32 {
+        while lines > 0 {
+            match self.incoming.front_mut() {
+                Some(garbage) if garbage.lines > lines => {
+                    garbage.lines -= lines;
+                    return 0;
+                }
+                Some(garbage) => lines -= garbage.lines,
+                None => break,
+            }
+            self.incoming.pop_front();
+        }
+        lines
+    }
+
+    /// Handles the garbage side of a lock. A clear cancels incoming garbage with its attack,
+    /// otherwise the garbage whose delay is over enters the board. Returns the lines to send to
+    /// the opponent, or `None` if the garbage topped the player out.
+    pub fn on_lock(
+        &mut self,
+        game: &mut Game,
+        table: &AttackTable,
+        clear: &LineClear,
+    ) -> Option<u32> {
+        if clear.lines > 0 {
+            return Some(self.cancel(table.attack(clear)));
+        }
+        while let Some(garbage) = self.incoming.front() {
+            if garbage.frames_left > 0 {
+                break;
+            }
+            if !insert_garbage(game, garbage.lines) {
+                return None;
+            }
+            self.incoming.pop_front();
+        }
+        Some(0)
+    }
+}
+
+/// Pushes `lines` garbage rows into `game`, all of them sharing a new random hole.
+pub fn insert_garbage(game: &mut Game, lines: u32) -> bool {
+    (0..lines).all(|i| {
+        let holes = if i == 0 {
+            HoleRule::RANDOM
+        } else {
+            HoleRule::SAME
+        };
+        game.insert_garbage_rows(1, holes)
+    })
+}
@@ -0,0 +1,159 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::attack::{AttackTable, GarbageQueue};
+use crate::game::{self, Action, Game, LineClear};
+
+/// One side of a versus match.
+#[derive(Clone)]
+pub struct Fighter {
+    pub game: Game,
+    pub garbage: GarbageQueue,
+    pub topped_out: bool,
+    /// Frames since the piece last went down.
+    gravity: u32,
+}
+
+impl Fighter {
+    pub fn new(seed: u64, garbage_delay: u32) -> Fighter {
+        let mut fighter = Fighter {
+            game: Game::with_seed(seed),
+            garbage: GarbageQueue::new(garbage_delay),
+            topped_out: false,
+            gravity: 0,
+        };
+        fighter.spawn();
+        fighter
+    }
+
+    fn spawn(&mut self) {
+        if self.game.current_piece.is_none() {
+            let current_piece = self.game.create_new_tetromino();
+            if !current_piece.test_current_position(&self.game.game_map) {
+                self.topped_out = true;
+            }
+            self.game.current_piece = Some(current_piece);
+        }
+    }
+
+    /// Applies the actions of this frame, then gravity. Returns the result of the lock if the
+    /// piece landed.
+    fn update(&mut self, actions: &[Action]) -> Option<LineClear> {
+        let mut lock = None;
+        for action in actions {
+            if *action == Action::SoftDrop {
+                self.gravity = 0;
+            }
+            if let Some(clear) = self.game.apply_action(*action) {
+                lock = Some(clear);
+            }
+        }
+        self.gravity += 1;
+        if self.gravity >= game::level_frames(self.game.current_level) {
+            self.gravity = 0;
+            if let Some(clear) = self.game.apply_action(Action::SoftDrop) {
+                lock = Some(clear);
+            }
+        }
+        if lock.is_some() {
+            self.gravity = 0;
+        }
+        lock
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Winner(usize),
+    Draw,
+}
+
+/// Two players exchanging garbage, advanced one frame at a time. Given the same seeds and the
+/// same actions every frame, two battles always end up in the same state.
+#[derive(Clone)]
+pub struct Battle {
+    pub fighters: [Fighter; 2],
+    pub frame: u32,
+    table: AttackTable,
+}
+
+impl Battle {
+    pub fn new(seeds: [u64; 2], garbage_delay: u32) -> Battle {
+        Battle {
+            fighters: [
+                Fighter::new(seeds[0], garbage_delay),
+                Fighter::new(seeds[1], garbage_delay),
+            ],
+            frame: 0,
+            table: AttackTable::default(),
+        }
+    }
+
+    /// Advances the battle by one frame with the actions of each player.
+    pub fn step(&mut self, actions: [&[Action]; 2]) {
+        if self.outcome().is_some() {
+            return;
+        }
+        let mut locks = [None, None];
+        for ((fighter, actions), lock) in self
+            .fighters
+            .iter_mut()
+            .zip(actions.iter())
+            .zip(locks.iter_mut())
+        {
+            *lock = fighter.update(actions);
+        }
+        for (index, lock) in locks.iter().enumerate() {
+            if let Some(ref clear) = *lock {
+                let fighter = &mut self.fighters[index];
+                match fighter
+                    .garbage
+                    .on_lock(&mut fighter.game, &self.table, clear)
+                {
+                    Some(sent) => self.fighters[1 - index].garbage.receive(sent),
+                    None => fighter.topped_out = true,
+                }
+            }
+        }
+        for fighter in self.fighters.iter_mut() {
+            fighter.garbage.tick();
+            if !fighter.topped_out {
+                fighter.spawn();
+            }
+        }
+        self.frame += 1;
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        match (self.fighters[0].topped_out, self.fighters[1].topped_out) {
+            (true, true) => Some(Outcome::Draw),
+            (true, false) => Some(Outcome::Winner(1)),
+            (false, true) => Some(Outcome::Winner(0)),
+            (false, false) => None,
+        }
+    }
+
+    /// Hash of everything the simulation depends on, used to check that two peers agree.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.frame.hash(&mut hasher);
+        for fighter in self.fighters.iter() {
+            fighter.game.game_map.hash(&mut hasher);
+            fighter.game.score.hash(&mut hasher);
+            fighter.game.num_lines.hash(&mut hasher);
+            if let Some(ref piece) = fighter.game.current_piece {
+                (piece.x, piece.y, piece.current_state).hash(&mut hasher);
+            }
+            if let Some(ref piece) = fighter.game.hold {
+                piece.kind().hash(&mut hasher);
+            }
+            fighter.game.can_hold.hash(&mut hasher);
+            for garbage in fighter.garbage.incoming.iter() {
+                (garbage.lines, garbage.frames_left).hash(&mut hasher);
+            }
+            fighter.topped_out.hash(&mut hasher);
+            fighter.gravity.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
@@ -0,0 +1,20 @@
+//! Reinforcement learning environment for trainers in other languages: reads `reset`, `step`
+//! and `close` requests as JSON lines on the standard input and answers on the standard output,
+//! see `tetrs::gym`.
+
+extern crate tetrs;
+
+use std::io;
+
+fn main() {
+    if let Some(arg) = std::env::args().nth(1) {
+        eprintln!("Unknown argument: {}", arg);
+        eprintln!("Usage: tetrs-gym");
+        return;
+    }
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = tetrs::gym::serve(stdin.lock(), stdout.lock()) {
+        eprintln!("Gym bridge failed: {}", e);
+    }
+}
@@ -0,0 +1,85 @@
+//! Match server for LAN games: clients join named rooms with `tetrs --server ADDRESS --room
+//! NAME` and play against the next client joining the same room.
+
+extern crate tetrs;
+
+use std::env;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+
+use tetrs::battle::Outcome;
+use tetrs::parse_value;
+use tetrs::server::{self, MatchResult, Report, ServerConfig};
+
+const DEFAULT_ADDRESS: &str = "0.0.0.0:7878";
+
+struct Args {
+    address: String,
+    config: ServerConfig,
+    self_test: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        address: DEFAULT_ADDRESS.to_owned(),
+        config: ServerConfig::default(),
+        self_test: false,
+    };
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--address" => args.address = parse_value(&arg, iter.next())?,
+            "--garbage-delay" => args.config.garbage_delay = parse_value(&arg, iter.next())?,
+            "--input-delay" => args.config.input_delay = parse_value(&arg, iter.next())?,
+            "--self-test" => args.self_test = true,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let Args {
+        address,
+        config,
+        self_test,
+    } = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: tetrs-server [--address ADDRESS] [--garbage-delay FRAMES] [--input-delay FRAMES] [--self-test]"
+            );
+            return;
+        }
+    };
+
+    if self_test {
+        match server::self_test(config, rand::random()) {
+            Ok(report) => print_report(&report),
+            Err(e) => eprintln!("Self test failed: {}", e),
+        }
+        return;
+    }
+
+    let listener = TcpListener::bind(&address).expect("Failed to bind the server address");
+    println!("Listening on {}", address);
+    let (sender, reports) = mpsc::channel();
+    thread::spawn(move || server::serve(listener, config, sender));
+    for report in reports {
+        print_report(&report);
+    }
+}
+
+fn print_report(report: &Report) {
+    let result = match report.result {
+        MatchResult::Finished(Outcome::Winner(player)) => format!("player {} wins", player + 1),
+        MatchResult::Finished(Outcome::Draw) => "draw".to_owned(),
+        MatchResult::Forfeit(player) => format!("player {} left", player + 1),
+    };
+    println!(
+        "Room {}: {} after {} frames (seed {})",
+        report.room, result, report.frames, report.seed
+    );
+}
@@ -0,0 +1,179 @@
+//! Headless batch simulator: plays many games with a bot, on every core and without a window,
+//! and reports how they went as JSON or CSV.
+
+extern crate serde;
+extern crate serde_json;
+extern crate tetrs;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use tetrs::bot::{BotSpec, BOT_DELAY};
+use tetrs::mode::{Mode, ModeArgs};
+use tetrs::sim::{self, GameReport, Summary};
+use tetrs::{parse_value, seconds_to_frames, seed_range};
+
+const DEFAULT_GAMES: u64 = 100;
+/// Games still going after this long are stopped, in seconds of play.
+const DEFAULT_MAX_SECONDS: u32 = 600;
+
+enum Format {
+    Json,
+    Csv,
+}
+
+struct Args {
+    bot: BotSpec,
+    bot_delay: u32,
+    mode: Mode,
+    /// A seed per game.
+    seeds: Range<u64>,
+    jobs: usize,
+    max_frames: u32,
+    format: Format,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut mode = ModeArgs::default();
+    let mut games = DEFAULT_GAMES;
+    let mut first_seed = 0;
+    let mut max_seconds = DEFAULT_MAX_SECONDS;
+    let mut args = Args {
+        bot: BotSpec::Greedy,
+        bot_delay: BOT_DELAY,
+        mode: mode.mode(),
+        seeds: 0..0,
+        jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        max_frames: 0,
+        format: Format::Json,
+        output: None,
+    };
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        if mode.parse(&arg, &mut iter)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--bot" => args.bot = parse_value(&arg, iter.next())?,
+            "--bot-delay" => args.bot_delay = parse_value(&arg, iter.next())?,
+            "--games" => games = parse_value(&arg, iter.next())?,
+            "--seed" => first_seed = parse_value(&arg, iter.next())?,
+            "--jobs" => args.jobs = parse_value(&arg, iter.next())?,
+            "--max-seconds" => max_seconds = parse_value(&arg, iter.next())?,
+            "--format" => {
+                args.format = match parse_value::<String>(&arg, iter.next())?.as_str() {
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    format => return Err(format!("Invalid value for --format: {}", format)),
+                }
+            }
+            "--output" => args.output = Some(parse_value(&arg, iter.next())?),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    args.mode = mode.mode();
+    args.seeds = seed_range(first_seed, games)?;
+    args.max_frames = seconds_to_frames("--max-seconds", max_seconds)?;
+    Ok(args)
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", usage());
+            return;
+        }
+    };
+
+    let start = Instant::now();
+    let reports = match sim::run(
+        &args.bot,
+        args.bot_delay,
+        args.mode,
+        args.seeds.clone(),
+        args.max_frames,
+        args.jobs,
+    ) {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("Failed to start the bot: {}", e);
+            return;
+        }
+    };
+    eprintln!(
+        "Played {} games in {:.1} seconds",
+        reports.len(),
+        start.elapsed().as_secs_f64()
+    );
+
+    let result = match args.output {
+        Some(ref path) => File::create(path).and_then(|file| write_reports(&args, &reports, file)),
+        None => write_reports(&args, &reports, io::stdout().lock()),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to write the reports: {}", e);
+    }
+}
+
+/// Everything written in JSON.
+#[derive(Serialize)]
+struct Batch<'a> {
+    bot: String,
+    mode: String,
+    first_seed: u64,
+    summary: Summary,
+    games: &'a [GameReport],
+}
+
+fn write_reports<W: Write>(args: &Args, reports: &[GameReport], mut output: W) -> io::Result<()> {
+    match args.format {
+        Format::Json => {
+            let batch = Batch {
+                bot: args.bot.to_string(),
+                mode: format!("{:?}", args.mode),
+                first_seed: args.seeds.start,
+                summary: sim::summarize(reports),
+                games: reports,
+            };
+            serde_json::to_writer_pretty(&mut output, &batch)?;
+            writeln!(output)
+        }
+        Format::Csv => {
+            writeln!(output, "seed,lines,score,pieces,seconds,ending")?;
+            for report in reports {
+                writeln!(
+                    output,
+                    "{},{},{},{},{:.2},{}",
+                    report.seed,
+                    report.lines,
+                    report.score,
+                    report.pieces,
+                    report.seconds(),
+                    report.ending.name()
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn usage() -> &'static str {
+    "Usage: tetrs-sim [--bot SPEC] [--bot-delay FRAMES] [--games N] [--seed FIRST] [--jobs N]
+                 [--max-seconds SECONDS] [--format json|csv] [--output FILE] [MODE]
+Bots: greedy, beam[:WIDTH[:MILLISECONDS]] or tbp:COMMAND. Games use seeds FIRST to
+FIRST + N - 1, and take the mode flags of tetrs:
+    --marathon [--line-cap LINES] [--endless]
+    --ultra [--time-limit SECONDS]
+    --dig [--lines LINES] [--hole-change PROBABILITY] [--messiness AMOUNT]
+    --survival [--interval SECONDS] [--hole-change PROBABILITY] [--messiness AMOUNT]
+    --zen [--no-gravity]"
+}
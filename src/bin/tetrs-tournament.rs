@@ -0,0 +1,151 @@
+//! Round-robin tournament between bots: every pair of bots plays versus matches on the same
+//! seeds, on every core, and the bots get Elo ratings from the results.
+
+extern crate serde;
+extern crate serde_json;
+extern crate tetrs;
+
+use std::env;
+use std::fs::File;
+use std::ops::Range;
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use tetrs::bot::{BotSpec, BOT_DELAY};
+use tetrs::tournament::{self, MatchLog, Standing};
+use tetrs::{parse_value, seconds_to_frames, seed_range};
+
+const DEFAULT_SEEDS: u64 = 10;
+/// Matches still going after this long are draws, in seconds of play.
+const DEFAULT_MAX_SECONDS: u32 = 300;
+
+struct Args {
+    bots: Vec<BotSpec>,
+    bot_delay: u32,
+    /// Seeds every pair of bots plays a match on.
+    seeds: Range<u64>,
+    jobs: usize,
+    max_frames: u32,
+    output: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut seeds = DEFAULT_SEEDS;
+    let mut first_seed = 0;
+    let mut max_seconds = DEFAULT_MAX_SECONDS;
+    let mut args = Args {
+        bots: Vec::new(),
+        bot_delay: BOT_DELAY,
+        seeds: 0..0,
+        jobs: thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+        max_frames: 0,
+        output: None,
+    };
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--bot" => args.bots.push(parse_value(&arg, iter.next())?),
+            "--bot-delay" => args.bot_delay = parse_value(&arg, iter.next())?,
+            "--seeds" => seeds = parse_value(&arg, iter.next())?,
+            "--seed" => first_seed = parse_value(&arg, iter.next())?,
+            "--jobs" => args.jobs = parse_value(&arg, iter.next())?,
+            "--max-seconds" => max_seconds = parse_value(&arg, iter.next())?,
+            "--output" => args.output = Some(parse_value(&arg, iter.next())?),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    if args.bots.len() < 2 {
+        return Err("A tournament needs at least two bots".to_owned());
+    }
+    args.seeds = seed_range(first_seed, seeds)?;
+    args.max_frames = seconds_to_frames("--max-seconds", max_seconds)?;
+    Ok(args)
+}
+
+/// Everything written to the output file.
+#[derive(Serialize)]
+struct Results<'a> {
+    bots: Vec<String>,
+    first_seed: u64,
+    standings: &'a [Standing],
+    matches: &'a [MatchLog],
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: tetrs-tournament --bot SPEC --bot SPEC... [--bot-delay FRAMES] [--seeds N] [--seed FIRST] [--jobs N] [--max-seconds SECONDS] [--output FILE]"
+            );
+            eprintln!("Bots: greedy, beam[:WIDTH[:MILLISECONDS]] or tbp:COMMAND.");
+            return;
+        }
+    };
+
+    let start = Instant::now();
+    let logs = match tournament::run(
+        &args.bots,
+        args.bot_delay,
+        args.seeds.clone(),
+        args.max_frames,
+        args.jobs,
+    ) {
+        Ok(logs) => logs,
+        Err(e) => {
+            eprintln!("Failed to start a bot: {}", e);
+            return;
+        }
+    };
+    for log in logs.iter() {
+        print_match(&args.bots, log);
+    }
+    println!(
+        "{} matches in {:.1} seconds",
+        logs.len(),
+        start.elapsed().as_secs_f64()
+    );
+
+    let standings = tournament::standings(&args.bots, &logs);
+    println!("Rank  Rating  Wins  Losses  Draws  Bot");
+    for (rank, standing) in standings.iter().enumerate() {
+        println!(
+            "{:>4}  {:>6.0}  {:>4}  {:>6}  {:>5}  {}",
+            rank + 1,
+            standing.rating,
+            standing.wins,
+            standing.losses,
+            standing.draws,
+            standing.bot
+        );
+    }
+
+    if let Some(path) = args.output {
+        let results = Results {
+            bots: args.bots.iter().map(BotSpec::to_string).collect(),
+            first_seed: args.seeds.start,
+            standings: &standings,
+            matches: &logs,
+        };
+        let result = File::create(&path)
+            .and_then(|file| serde_json::to_writer_pretty(file, &results).map_err(Into::into));
+        if let Err(e) = result {
+            eprintln!("Failed to write {}: {}", path, e);
+        }
+    }
+}
+
+fn print_match(bots: &[BotSpec], log: &MatchLog) {
+    let [first, second] = log.bots;
+    let result = match log.winner {
+        Some(winner) => format!("{} wins", bots[winner]),
+        None => "draw".to_owned(),
+    };
+    println!(
+        "Seed {}: {} ({} lines) vs {} ({} lines), {} after {} frames",
+        log.seed, bots[first], log.lines[0], bots[second], log.lines[1], result, log.frames
+    );
+}
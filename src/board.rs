@@ -0,0 +1,140 @@
+//! Boards as text, a line per row from top to bottom: `.` for empty cases, the letter of the
+//! piece for the cases of each (`IJLOSZT`, by shape as in the bot protocol) and `G` for
+//! garbage. Blank lines and lines starting with `#` are left out, so files can have comments.
+
+use crate::game::{Game, GARBAGE};
+use crate::tbp;
+
+/// `game_map` as text, every row ending with a new line.
+pub fn render(game_map: &[Vec<u8>]) -> String {
+    let mut text = String::new();
+    for row in game_map {
+        text.extend(row.iter().map(|case| letter(*case)));
+        text.push('\n');
+    }
+    text
+}
+
+/// The rows written in `text`, from top to bottom. Fails on unknown characters and on rows of
+/// different widths.
+pub fn parse(text: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut rows: Vec<Vec<u8>> = Vec::new();
+    let lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for (number, line) in lines.enumerate() {
+        let row = line
+            .chars()
+            .map(|letter| case(letter).ok_or_else(|| format!("Unknown case: {}", letter)))
+            .collect::<Result<Vec<u8>, _>>()?;
+        if let Some(first) = rows.first() {
+            if row.len() != first.len() {
+                return Err(format!(
+                    "Row {} is {} cases wide instead of {}",
+                    number + 1,
+                    row.len(),
+                    first.len()
+                ));
+            }
+        }
+        rows.push(row);
+    }
+    if rows.is_empty() {
+        return Err("The board has no row".to_owned());
+    }
+    Ok(rows)
+}
+
+/// The character of `case` in text boards.
+pub fn letter(case: u8) -> char {
+    match case {
+        0 => '.',
+        case => tbp::letter(case).unwrap_or('G'),
+    }
+}
+
+/// The case written `letter` in text boards.
+pub fn case(letter: char) -> Option<u8> {
+    match letter {
+        '.' => Some(0),
+        'G' => Some(GARBAGE),
+        _ => (1..=7).find(|kind| tbp::letter(*kind) == Some(letter)),
+    }
+}
+
+/// Puts `rows` at the bottom of the board of `game`, the rows above them left empty. Fails if
+/// they are wider or higher than the board, or if a row is already complete.
+pub fn load(rows: &[Vec<u8>], game: &mut Game) -> Result<(), String> {
+    let height = game.game_map.len();
+    let columns = game.game_map.first().map_or(0, Vec::len);
+    if rows.len() > height || rows.iter().any(|row| row.len() != columns) {
+        return Err(format!(
+            "The board must be {} cases wide and at most {} rows high",
+            columns, height
+        ));
+    }
+    if let Some(number) = rows.iter().position(|row| !row.contains(&0)) {
+        return Err(format!("Row {} is complete", number + 1));
+    }
+    for row in game.game_map[..height - rows.len()].iter_mut() {
+        *row = vec![0; columns];
+    }
+    game.game_map[height - rows.len()..].clone_from_slice(rows);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETUP: &str = "\
+........OO
+LL.....IOO
+LGG....IOO
+";
+
+    #[test]
+    fn render_writes_back_what_parse_read() {
+        let rows = parse(SETUP).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2][0], 2);
+        assert_eq!(rows[2][1], GARBAGE);
+        assert_eq!(render(&rows), SETUP);
+        assert_eq!(parse(&render(&rows)).unwrap(), rows);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let text = format!("# A setup\n\n{}\n", SETUP);
+        assert_eq!(parse(&text).unwrap(), parse(SETUP).unwrap());
+    }
+
+    #[test]
+    fn parse_rejects_bad_boards() {
+        assert!(parse("..X.......").is_err());
+        assert!(parse("..........\n.........").is_err());
+        assert!(parse("# nothing").is_err());
+    }
+
+    #[test]
+    fn load_puts_rows_at_the_bottom() {
+        let mut game = Game::with_seed(0);
+        load(&parse(SETUP).unwrap(), &mut game).unwrap();
+        let height = game.game_map.len();
+        assert_eq!(render(&game.game_map[height - 3..]), SETUP);
+        assert!(game.game_map[..height - 3]
+            .iter()
+            .flatten()
+            .all(|case| *case == 0));
+    }
+
+    #[test]
+    fn load_rejects_complete_rows() {
+        let rows = parse("GGGGGGGGGG\nGGGG.GGGGG").unwrap();
+        assert_eq!(
+            load(&rows, &mut Game::with_seed(0)),
+            Err("Row 1 is complete".to_owned())
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::attack;
+use crate::battle::Battle;
+use crate::game::{Action, Game};
+use crate::movegen::{placements, Placement};
+use crate::search::{self, Search};
+use crate::tbp::ExternalBot;
+use crate::tetromino::Tetromino;
+
+/// Frames between two actions of the bot, which would play instantly otherwise.
+pub const BOT_DELAY: u32 = 6;
+
+/// Weight of every board feature in the evaluation of a placement, the best placement having
+/// the highest score. The defaults are the El-Tetris weights for the six Dellacherie
+/// features, plus small penalties for the height and bumpiness of the stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub landing_height: f64,
+    pub eroded_cells: f64,
+    pub row_transitions: f64,
+    pub column_transitions: f64,
+    pub holes: f64,
+    pub wells: f64,
+    pub aggregate_height: f64,
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights {
+            landing_height: -4.500158825082766,
+            eroded_cells: 3.4181268101392694,
+            row_transitions: -3.2178882868487753,
+            column_transitions: -9.348695305445199,
+            holes: -7.899265427351652,
+            wells: -3.3855972247263626,
+            aggregate_height: -0.1,
+            bumpiness: -0.1,
+        }
+    }
+}
+
+/// Board features of a placement, see `Weights`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Features {
+    pub landing_height: f64,
+    pub eroded_cells: u32,
+    pub row_transitions: u32,
+    pub column_transitions: u32,
+    pub holes: u32,
+    pub wells: u32,
+    pub aggregate_height: u32,
+    pub bumpiness: u32,
+}
+
+impl Weights {
+    pub fn score(&self, features: &Features) -> f64 {
+        self.landing_height * features.landing_height
+            + self.eroded_cells * features.eroded_cells as f64
+            + self.row_transitions * features.row_transitions as f64
+            + self.column_transitions * features.column_transitions as f64
+            + self.holes * features.holes as f64
+            + self.wells * features.wells as f64
+            + self.aggregate_height * features.aggregate_height as f64
+            + self.bumpiness * features.bumpiness as f64
+    }
+}
+
+/// Copies `game_map` with `piece` locked at (`x`, `y`) in `state`, without clearing lines.
+pub fn lock_piece(
+    game_map: &[Vec<u8>],
+    piece: &Tetromino,
+    state: usize,
+    x: isize,
+    y: usize,
+) -> Vec<Vec<u8>> {
+    let mut board = game_map.to_vec();
+    for (line_num, line) in piece.states[state].iter().enumerate() {
+        for (case_num, case) in line.iter().enumerate() {
+            if *case != 0 {
+                board[y + line_num][(x + case_num as isize) as usize] = *case;
+            }
+        }
+    }
+    board
+}
+
+/// Measures the board left by a placement.
+pub fn features(game_map: &[Vec<u8>], piece: &Tetromino, placement: &Placement) -> Features {
+    let state = placement.state as usize;
+    let mut board = lock_piece(game_map, piece, state, placement.x, placement.y);
+    measure(&mut board, piece, placement)
+}
+
+/// Like `features`, for a `board` where the piece is already locked. Clears its lines.
+pub(crate) fn measure(
+    board: &mut Vec<Vec<u8>>,
+    piece: &Tetromino,
+    placement: &Placement,
+) -> Features {
+    let state = placement.state as usize;
+    let rows = board.len();
+    let columns = board[0].len();
+
+    let piece_rows: Vec<usize> = piece.states[state]
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.iter().any(|case| *case != 0))
+        .map(|(line_num, _)| placement.y + line_num)
+        .collect();
+    let top = piece_rows.first().cloned().unwrap_or(0);
+    let bottom = piece_rows.last().cloned().unwrap_or(0);
+    let landing_height = rows as f64 - (top + bottom) as f64 / 2.0;
+
+    let mut cleared = 0;
+    let mut piece_cells = 0;
+    for (line_num, line) in piece.states[state].iter().enumerate() {
+        let row = placement.y + line_num;
+        if row < rows && board[row].iter().all(|case| *case != 0) {
+            cleared += 1;
+            piece_cells += line.iter().filter(|case| **case != 0).count() as u32;
+        }
+    }
+    board.retain(|row| row.contains(&0));
+    while board.len() < rows {
+        board.insert(0, vec![0; columns]);
+    }
+
+    let filled = |x: isize, y: usize| {
+        x < 0 || x as usize >= columns || y >= rows || board[y][x as usize] != 0
+    };
+    let mut features = Features {
+        landing_height,
+        eroded_cells: cleared * piece_cells,
+        ..Features::default()
+    };
+    for y in 0..rows {
+        for x in 0..=columns as isize {
+            if filled(x - 1, y) != filled(x, y) {
+                features.row_transitions += 1;
+            }
+        }
+    }
+    let mut heights = Vec::with_capacity(columns);
+    for x in 0..columns as isize {
+        let mut covered = false;
+        let mut previous = false;
+        let mut well = 0;
+        let mut height = 0;
+        for y in 0..=rows {
+            let case = filled(x, y);
+            if case != previous {
+                features.column_transitions += 1;
+            }
+            previous = case;
+            if y == rows {
+                break;
+            }
+            if case {
+                if !covered {
+                    height = rows - y;
+                }
+                covered = true;
+                well = 0;
+            } else {
+                if covered {
+                    features.holes += 1;
+                }
+                if filled(x - 1, y) && filled(x + 1, y) {
+                    well += 1;
+                    features.wells += well;
+                } else {
+                    well = 0;
+                }
+            }
+        }
+        heights.push(height as u32);
+    }
+    features.aggregate_height = heights.iter().sum();
+    features.bumpiness = heights
+        .windows(2)
+        .map(|pair| (pair[0] as i32 - pair[1] as i32).unsigned_abs())
+        .sum();
+    features
+}
+
+/// How the bot chooses where to put its pieces.
+#[derive(Clone, Debug)]
+pub enum Strategy {
+    /// Where the board looks best right after the current piece.
+    Greedy,
+    /// Where the board looks best a few pieces ahead, see `search`.
+    Beam(Search),
+    /// Wherever an external bot engine says, see `tbp`.
+    External(Arc<Mutex<ExternalBot>>),
+}
+
+/// A bot given on the command line: `greedy`, `beam`, `beam:WIDTH`,
+/// `beam:WIDTH:MILLISECONDS` or `tbp:COMMAND`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BotSpec {
+    Greedy,
+    Beam(Search),
+    Tbp(String),
+}
+
+impl FromStr for BotSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<BotSpec, String> {
+        let invalid = || format!("Invalid bot: {}", spec);
+        if spec == "greedy" {
+            return Ok(BotSpec::Greedy);
+        }
+        if let Some(command) = spec.strip_prefix("tbp:") {
+            return Ok(BotSpec::Tbp(command.to_owned()));
+        }
+        let mut parts = spec.split(':');
+        if parts.next() != Some("beam") {
+            return Err(invalid());
+        }
+        let mut search = Search::default();
+        if let Some(width) = parts.next() {
+            search.width = width.parse().map_err(|_| invalid())?;
+        }
+        if let Some(time) = parts.next() {
+            search.time_budget = Some(Duration::from_millis(time.parse().map_err(|_| invalid())?));
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(BotSpec::Beam(search))
+    }
+}
+
+impl fmt::Display for BotSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BotSpec::Greedy => write!(f, "greedy"),
+            BotSpec::Beam(ref search) => match search.time_budget {
+                Some(budget) => write!(f, "beam:{}:{}", search.width, budget.as_millis()),
+                None => write!(f, "beam:{}", search.width),
+            },
+            BotSpec::Tbp(ref command) => write!(f, "tbp:{}", command),
+        }
+    }
+}
+
+impl BotSpec {
+    /// A new bot playing one action every `delay` frames. Starts the engine of TBP bots.
+    pub fn build(&self, delay: u32) -> io::Result<Bot> {
+        let mut bot = Bot::new(delay);
+        bot.strategy = match *self {
+            BotSpec::Greedy => Strategy::Greedy,
+            BotSpec::Beam(ref search) => Strategy::Beam(search.clone()),
+            BotSpec::Tbp(ref command) => {
+                Strategy::External(Arc::new(Mutex::new(ExternalBot::launch(command)?)))
+            }
+        };
+        Ok(bot)
+    }
+}
+
+/// Plays a game by itself, placing each piece where the board looks best afterwards.
+#[derive(Clone, Debug)]
+pub struct Bot {
+    pub weights: Weights,
+    pub strategy: Strategy,
+    /// Frames between two actions.
+    pub delay: u32,
+    plan: VecDeque<Action>,
+    wait: u32,
+    /// Row the current piece should be on after the last action. A piece higher than that is
+    /// a new one, and needs a new plan, while a lower one was pulled down by gravity.
+    piece_y: usize,
+}
+
+impl Bot {
+    pub fn new(delay: u32) -> Bot {
+        Bot {
+            weights: Weights::default(),
+            strategy: Strategy::Greedy,
+            delay,
+            plan: VecDeque::new(),
+            wait: 0,
+            piece_y: 0,
+        }
+    }
+
+    /// The best placement of the current piece of `game`, if it has one.
+    pub fn best_placement(&self, game: &Game) -> Option<Placement> {
+        match self.strategy {
+            Strategy::Greedy => {}
+            Strategy::Beam(ref config) => {
+                return search::best_placement(game, &self.weights, config);
+            }
+            Strategy::External(ref external) => {
+                let mut external = external.lock().expect("External bot poisoned");
+                return match external.best_placement(game) {
+                    Ok(placement) => placement,
+                    Err(e) => {
+                        eprintln!("{} stopped playing: {}", external.name, e);
+                        None
+                    }
+                };
+            }
+        }
+        let piece = game.current_piece.as_ref()?;
+        let mut best: Option<(f64, Placement)> = None;
+        for placement in placements(&game.game_map, piece) {
+            let score = self
+                .weights
+                .score(&features(&game.game_map, piece, &placement));
+            match best {
+                Some((best_score, _)) if score <= best_score => {}
+                _ => best = Some((score, placement)),
+            }
+        }
+        best.map(|(_, placement)| placement)
+    }
+
+    /// Actions to apply to `game` during this frame.
+    pub fn next_actions(&mut self, game: &Game) -> Vec<Action> {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return Vec::new();
+        }
+        let piece_y = match game.current_piece {
+            Some(ref piece) => piece.y,
+            None => return Vec::new(),
+        };
+        if self.plan.is_empty() || piece_y < self.piece_y {
+            self.plan = self
+                .best_placement(game)
+                .map(|placement| placement.actions.into())
+                .unwrap_or_default();
+        } else {
+            // Soft drops planned for the rows gravity already took would lock the piece early.
+            for _ in self.piece_y..piece_y {
+                match self
+                    .plan
+                    .iter()
+                    .position(|action| *action == Action::SoftDrop)
+                {
+                    Some(index) => {
+                        self.plan.remove(index);
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.piece_y = piece_y;
+        match self.plan.pop_front() {
+            Some(action) => {
+                match action {
+                    Action::SoftDrop => self.piece_y += 1,
+                    // The next piece spawns at the top, and is part of the plan.
+                    Action::Hold => self.piece_y = 0,
+                    _ => {}
+                }
+                self.wait = self.delay;
+                vec![action]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Plays a versus match between two bots, the pieces of both players coming from `seed`,
+/// until someone tops out or `max_frames` frames have passed. Returns the battle as it ended.
+pub fn duel(bots: [&mut Bot; 2], seed: u64, max_frames: u32) -> Battle {
+    let mut battle = Battle::new([seed, seed], attack::GARBAGE_DELAY);
+    let [first, second] = bots;
+    while battle.frame < max_frames && battle.outcome().is_none() {
+        let actions = [
+            first.next_actions(&battle.fighters[0].game),
+            second.next_actions(&battle.fighters[1].game),
+        ];
+        battle.step([&actions[0], &actions[1]]);
+    }
+    battle
+}
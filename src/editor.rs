@@ -0,0 +1,136 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::options::Options;
+use crate::render::Renderer;
+use tetrs::game::{Game, GARBAGE, PREVIEW_SIZE};
+use tetrs::{board, fumen, tetromino};
+
+/// Case painted when the editor opens, a T.
+const DEFAULT_BRUSH: u8 = 7;
+
+/// Lets the player paint the board of `game` with the mouse and choose its queue and hold,
+/// then play it as many times as wanted, until the player quits.
+pub fn run_editor(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    options: &Options,
+    mut game: Game,
+) {
+    let mut brush = DEFAULT_BRUSH;
+    // Why the board can't be played, until it gets edited.
+    let mut warning: Option<String> = None;
+    // Pieces of the queue chosen by the player, the current piece of a fumen coming first.
+    let mut pieces: Vec<u8> = game
+        .current_piece
+        .take()
+        .map(|piece| piece.kind())
+        .into_iter()
+        .collect();
+    if !pieces.is_empty() {
+        game.set_queue(&pieces);
+    }
+
+    loop {
+        let mut play = false;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return,
+                Event::MouseButtonDown {
+                    mouse_btn, x, y, ..
+                } => {
+                    warning = None;
+                    match mouse_btn {
+                        MouseButton::Left => paint(renderer, &mut game, x, y, brush),
+                        MouseButton::Right => paint(renderer, &mut game, x, y, 0),
+                        _ => {}
+                    }
+                }
+                Event::MouseMotion {
+                    mousestate, x, y, ..
+                } => {
+                    if mousestate.left() {
+                        paint(renderer, &mut game, x, y, brush);
+                    } else if mousestate.right() {
+                        paint(renderer, &mut game, x, y, 0);
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::Return => play = true,
+                    Keycode::N if brush != GARBAGE && pieces.len() < PREVIEW_SIZE => {
+                        pieces.push(brush);
+                        game.set_queue(&pieces);
+                    }
+                    Keycode::Backspace if !pieces.is_empty() => {
+                        pieces.pop();
+                        game.set_queue(&pieces);
+                    }
+                    // Garbage empties the hold.
+                    Keycode::H => game.hold = tetromino::from_kind(brush),
+                    Keycode::Delete => {
+                        warning = None;
+                        game.clear_board();
+                    }
+                    Keycode::F => println!("{}", fumen::export(&game)),
+                    Keycode::B => print!("{}", board::render(&game.game_map)),
+                    _ => {
+                        let name = keycode.name();
+                        let mut letters = name.chars();
+                        if let (Some(letter), None) = (letters.next(), letters.next()) {
+                            if let Some(case) = board::case(letter).filter(|case| *case != 0) {
+                                brush = case;
+                            }
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if play {
+            // Complete rows would never get cleared, as only locking pieces clears lines.
+            match board::load(&game.game_map, &mut game.clone()) {
+                Ok(()) => {
+                    crate::play_single(renderer, event_pump, options, game.clone());
+                    continue;
+                }
+                Err(error) => warning = Some(error.to_uppercase()),
+            }
+        }
+        renderer.hint = None;
+        let mut texts = vec![
+            format!("BRUSH: {}", board::letter(brush)),
+            "CLICK: PAINT".to_owned(),
+            "RIGHT CLICK: ERASE".to_owned(),
+            "IJLOSZTG: BRUSH".to_owned(),
+            "N: ADD TO NEXT".to_owned(),
+            "BACKSPACE: POP NEXT".to_owned(),
+            "H: HOLD BRUSH".to_owned(),
+            "DELETE: CLEAR".to_owned(),
+            "ENTER: PLAY".to_owned(),
+            "F: FUMEN  B: TEXT".to_owned(),
+        ];
+        if let Some(ref warning) = warning {
+            texts.insert(0, warning.clone());
+        }
+        renderer.draw_boards(&[(&game, &texts)]);
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Sets the case under the pixel (`x`, `y`), if any, to `case`.
+fn paint(renderer: &Renderer, game: &mut Game, x: i32, y: i32, case: u8) {
+    if let Some((row, column)) = renderer.case_at(game, x, y) {
+        game.game_map[row][column] = case;
+    }
+}
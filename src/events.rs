@@ -2,18 +2,156 @@ use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use std::time::SystemTime;
 
-use crate::game::Game;
+use crate::finesse::{AutoShift, Input};
+use crate::game::{Action, Game, LineClear};
 
-pub fn handle_events(
-    game: &mut Game,
-    quit: &mut bool,
-    timer: &mut SystemTime,
+/// Shifts played in one frame when the auto repeat rate is 0, enough to cross the board.
+const SHIFT_TO_WALL: usize = 10;
+
+/// Keys bound to each action for one player.
+pub struct KeyMap {
+    pub left: Keycode,
+    pub right: Keycode,
+    pub soft_drop: Keycode,
+    pub rotate: Keycode,
+    pub hard_drop: Keycode,
+    pub hold: Keycode,
+}
+
+impl KeyMap {
+    pub fn single_player() -> KeyMap {
+        KeyMap {
+            left: Keycode::Left,
+            right: Keycode::Right,
+            soft_drop: Keycode::Down,
+            rotate: Keycode::Up,
+            hard_drop: Keycode::Space,
+            hold: Keycode::C,
+        }
+    }
+
+    pub fn player_one() -> KeyMap {
+        KeyMap {
+            left: Keycode::A,
+            right: Keycode::D,
+            soft_drop: Keycode::S,
+            rotate: Keycode::W,
+            hard_drop: Keycode::Space,
+            hold: Keycode::LShift,
+        }
+    }
+
+    pub fn player_two() -> KeyMap {
+        KeyMap {
+            left: Keycode::Left,
+            right: Keycode::Right,
+            soft_drop: Keycode::Down,
+            rotate: Keycode::Up,
+            hard_drop: Keycode::Return,
+            hold: Keycode::RShift,
+        }
+    }
+
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        if keycode == self.left {
+            Some(Action::Left)
+        } else if keycode == self.right {
+            Some(Action::Right)
+        } else if keycode == self.soft_drop {
+            Some(Action::SoftDrop)
+        } else if keycode == self.rotate {
+            Some(Action::Rotate)
+        } else if keycode == self.hard_drop {
+            Some(Action::HardDrop)
+        } else if keycode == self.hold {
+            Some(Action::Hold)
+        } else {
+            None
+        }
+    }
+}
+
+/// Collects the actions pressed during this frame, one list per entry of `keymaps`. Escape
+/// quits, backspace undoes and F asks for the fumen of the boards.
+pub fn poll_actions(
     event_pump: &mut sdl2::EventPump,
-) -> bool {
-    let mut make_permanent = false;
-    if let Some(ref mut piece) = game.current_piece {
-        let mut tmp_x = piece.x;
-        let mut tmp_y = piece.y;
+    keymaps: &[KeyMap],
+    quit: &mut bool,
+    undo: &mut bool,
+    export: &mut bool,
+) -> Vec<Vec<Action>> {
+    let mut actions = keymaps.iter().map(|_| Vec::new()).collect::<Vec<_>>();
+
+    for event in event_pump.poll_iter() {
+        match event {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => {
+                *quit = true;
+                break;
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => {
+                *undo = true;
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::F),
+                ..
+            } => {
+                *export = true;
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => {
+                for (player, keymap) in keymaps.iter().enumerate() {
+                    if let Some(action) = keymap.action(keycode) {
+                        actions[player].push(action);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    actions
+}
+
+/// Keys of one player played with delayed auto shift: a held left or right key shifts the
+/// piece on its own once held for the delay, then at the repeat rate, and a held soft drop
+/// drops it every frame. Key repeats of the system are left out.
+pub struct AutoRepeat {
+    keymap: KeyMap,
+    shift: AutoShift,
+    /// Left or right, whichever was pressed last and is still held, and the frames until it
+    /// shifts the piece again.
+    held: Option<(Action, u32)>,
+    soft_drop: bool,
+}
+
+impl AutoRepeat {
+    pub fn new(keymap: KeyMap, shift: AutoShift) -> AutoRepeat {
+        AutoRepeat {
+            keymap,
+            shift,
+            held: None,
+            soft_drop: false,
+        }
+    }
+
+    /// Collects the keys pressed during this frame followed by the ones played by held keys.
+    /// Escape quits, backspace restarts and F asks for the fumen of the board.
+    pub fn poll(
+        &mut self,
+        event_pump: &mut sdl2::EventPump,
+        quit: &mut bool,
+        restart: &mut bool,
+        export: &mut bool,
+    ) -> Vec<Input> {
+        let mut inputs = Vec::new();
 
         for event in event_pump.poll_iter() {
             match event {
@@ -26,54 +164,107 @@ pub fn handle_events(
                     break;
                 }
                 Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => {
-                    *timer = SystemTime::now();
-                    tmp_y += 1;
-                }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
+                    keycode: Some(Keycode::Backspace),
                     ..
                 } => {
-                    tmp_x += 1;
+                    *restart = true;
                 }
                 Event::KeyDown {
-                    keycode: Some(Keycode::Left),
+                    keycode: Some(Keycode::F),
                     ..
                 } => {
-                    tmp_x -= 1;
+                    *export = true;
                 }
                 Event::KeyDown {
-                    keycode: Some(Keycode::Up),
+                    keycode: Some(keycode),
+                    repeat: false,
                     ..
                 } => {
-                    piece.rotate(&game.game_map);
+                    if let Some(action) = self.keymap.action(keycode) {
+                        inputs.push(Input {
+                            action,
+                            press: true,
+                        });
+                        match action {
+                            Action::Left | Action::Right => {
+                                self.held = Some((action, self.shift.das))
+                            }
+                            Action::SoftDrop => self.soft_drop = true,
+                            _ => {}
+                        }
+                    }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Space),
+                Event::KeyUp {
+                    keycode: Some(keycode),
                     ..
-                } => {
-                    let x = piece.x;
-                    let mut y = piece.y;
-
-                    while piece.change_position(&game.game_map, x, y + 1) == true {
-                        y += 1;
+                } => match self.keymap.action(keycode) {
+                    Some(Action::SoftDrop) => self.soft_drop = false,
+                    Some(action) if self.held.map(|(held, _)| held) == Some(action) => {
+                        self.held = None
                     }
-                    make_permanent = true;
-                }
+                    _ => {}
+                },
                 _ => {}
             }
         }
-        if !make_permanent {
-            if piece.change_position(&game.game_map, tmp_x, tmp_y) == false && tmp_y != piece.y {
-                make_permanent = true;
+
+        if let Some((action, ref mut frames)) = self.held {
+            *frames = frames.saturating_sub(1);
+            if *frames == 0 {
+                let shifts = if self.shift.arr == 0 {
+                    SHIFT_TO_WALL
+                } else {
+                    1
+                };
+                for _ in 0..shifts {
+                    inputs.push(Input {
+                        action,
+                        press: false,
+                    });
+                }
+                *frames = self.shift.arr;
             }
         }
+        if self.soft_drop {
+            inputs.push(Input {
+                action: Action::SoftDrop,
+                press: false,
+            });
+        }
+        inputs
+    }
+}
+
+/// Applies `actions` to `game`, restarting the gravity timer on soft drops and locks.
+/// Returns the result of the lock if the current piece got locked.
+pub fn apply_actions(
+    game: &mut Game,
+    actions: &[Action],
+    timer: &mut SystemTime,
+) -> Option<LineClear> {
+    let mut lock = None;
+    for action in actions {
+        if *action == Action::SoftDrop {
+            *timer = SystemTime::now();
+        }
+        if let Some(clear) = game.apply_action(*action) {
+            lock = Some(clear);
+        }
     }
-    if make_permanent {
-        game.make_permanent();
+    if lock.is_some() {
         *timer = SystemTime::now();
     }
-    make_permanent
+    lock
+}
+
+pub fn handle_events(
+    game: &mut Game,
+    quit: &mut bool,
+    undo: &mut bool,
+    export: &mut bool,
+    timer: &mut SystemTime,
+    event_pump: &mut sdl2::EventPump,
+) -> Option<LineClear> {
+    let actions = poll_actions(event_pump, &[KeyMap::single_player()], quit, undo, export);
+    apply_actions(game, &actions[0], timer)
 }
@@ -0,0 +1,43 @@
+//! The `key: value` files puzzles and openers are written in, and the directories holding them.
+
+use std::fs;
+use std::path::Path;
+
+/// The key and value of a `key: value` line of a puzzle or opener file.
+pub(crate) fn key_value(line: &str) -> Result<(&str, &str), String> {
+    match line.find(':') {
+        Some(at) => Ok((&line[..at], line[at + 1..].trim())),
+        None => Err(format!("Expected a key and a value: {}", line)),
+    }
+}
+
+/// What `parse` reads from the files of `dir` with `extension`, sorted by file name. `parse`
+/// gets the name of each file without its extension, then its text.
+pub(crate) fn load_files<T>(
+    dir: &Path,
+    extension: &str,
+    parse: fn(&str, &str) -> Result<T, String>,
+) -> Result<Vec<T>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.extension().and_then(|extension| extension.to_str()) == Some(extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| {
+            let id = path
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+            fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| parse(&id, &text))
+                .map_err(|e| format!("{}: {}", path.display(), e))
+        })
+        .collect()
+}
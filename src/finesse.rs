@@ -0,0 +1,266 @@
+//! Finesse: placing every piece with as few key presses as possible. The least number of
+//! presses for each placement is found with `movegen::least_presses`, counting a held left or
+//! right as one press taking the piece as far as the auto shift settings let it stop, and
+//! compared with the keys the player pressed to place it.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::board;
+use crate::game::{Action, Game, LineClear};
+use crate::movegen;
+use crate::tetromino::Tetromino;
+
+/// Frames a left or right key is held before the piece starts shifting on its own.
+pub const DAS: u32 = 10;
+/// Frames between two shifts once they started, 0 shifting to the wall at once.
+pub const ARR: u32 = 2;
+/// Frames a player needs to let go of a held key between two shifts.
+const RELEASE: u32 = 6;
+
+/// Delayed auto shift settings of the input layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoShift {
+    pub das: u32,
+    pub arr: u32,
+}
+
+impl AutoShift {
+    /// Whether shifts come slowly enough for a held key to be let go on any column, rather
+    /// than only once the piece stops.
+    pub fn stops_anywhere(&self) -> bool {
+        self.arr >= RELEASE
+    }
+}
+
+impl Default for AutoShift {
+    fn default() -> AutoShift {
+        AutoShift { das: DAS, arr: ARR }
+    }
+}
+
+/// An action played by the input layer, either because its key was just pressed or because
+/// the key is held.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Input {
+    pub action: Action,
+    pub press: bool,
+}
+
+/// Pieces locked in one orientation and how they were placed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tally {
+    pub pieces: u32,
+    /// Pieces placed with more presses than needed.
+    pub faults: u32,
+    /// Presses beyond the least needed, over all pieces.
+    pub wasted: u32,
+}
+
+/// A piece placed with more presses than needed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fault {
+    pub kind: u8,
+    pub state: u8,
+    pub presses: u32,
+    pub least: u32,
+}
+
+/// Finesse of the pieces placed so far in a game played through `apply_input`.
+#[derive(Clone, Debug, Default)]
+pub struct Finesse {
+    /// Tallies by piece kind and orientation.
+    pub tallies: BTreeMap<(u8, u8), Tally>,
+    pub last_fault: Option<Fault>,
+    /// Presses since the current piece spawned.
+    presses: u32,
+    /// Least presses for each placement of the current piece, found on its first input.
+    least: Option<HashMap<Vec<(usize, usize)>, u32>>,
+    /// Settings of the input layer the presses are played with.
+    shift: AutoShift,
+}
+
+impl Finesse {
+    pub fn new(shift: AutoShift) -> Finesse {
+        Finesse {
+            shift,
+            ..Finesse::default()
+        }
+    }
+
+    /// Plays `input` on `game`, counting it if it is a press, and checks the finesse of the
+    /// piece if it got locked. A held soft drop stops on the floor instead of locking the
+    /// piece. Holding starts the count over with the new piece.
+    pub fn apply_input(&mut self, game: &mut Game, input: Input) -> Option<LineClear> {
+        let piece = game.current_piece.clone()?;
+        if input.action == Action::Hold {
+            if game.can_hold {
+                self.spawned();
+            }
+            return game.apply_action(Action::Hold);
+        }
+        let landing = landing(&game.game_map, &piece);
+        if input.action == Action::SoftDrop && !input.press && landing == piece.y {
+            return None;
+        }
+        if self.least.is_none() {
+            self.least = Some(movegen::least_presses(&game.game_map, &piece, self.shift));
+        }
+        if input.press {
+            self.presses += 1;
+        }
+
+        let clear = game.apply_action(input.action)?;
+        let cases = movegen::cases(&piece, piece.current_state, piece.x, landing);
+        let least = self.least.as_ref().and_then(|least| least.get(&cases));
+        if let Some(&least) = least {
+            self.tally(piece.kind(), piece.current_state, least);
+        }
+        self.spawned();
+        Some(clear)
+    }
+
+    /// Forgets the presses of the current piece, as a new one is coming.
+    pub fn spawned(&mut self) {
+        self.presses = 0;
+        self.least = None;
+    }
+
+    fn tally(&mut self, kind: u8, state: u8, least: u32) {
+        let tally = self.tallies.entry((kind, state)).or_default();
+        tally.pieces += 1;
+        if self.presses > least {
+            tally.faults += 1;
+            tally.wasted += self.presses - least;
+            self.last_fault = Some(Fault {
+                kind,
+                state,
+                presses: self.presses,
+                least,
+            });
+        }
+    }
+
+    pub fn pieces(&self) -> u32 {
+        self.tallies.values().map(|tally| tally.pieces).sum()
+    }
+
+    pub fn faults(&self) -> u32 {
+        self.tallies.values().map(|tally| tally.faults).sum()
+    }
+
+    /// Percentage of the pieces placed without a fault, 100 before the first one.
+    pub fn accuracy(&self) -> u32 {
+        match self.pieces() {
+            0 => 100,
+            pieces => (pieces - self.faults()) * 100 / pieces,
+        }
+    }
+
+    /// A line per orientation pieces got locked in, the ones with the most faults first.
+    pub fn summary(&self) -> Vec<String> {
+        let mut tallies: Vec<_> = self.tallies.iter().collect();
+        tallies.sort_by_key(|(_, tally)| std::cmp::Reverse(tally.faults));
+        tallies
+            .into_iter()
+            .map(|(&(kind, state), tally)| {
+                format!(
+                    "{} ORIENTATION {}: {}/{} FAULTS, {} KEYS WASTED",
+                    board::letter(kind),
+                    state,
+                    tally.faults,
+                    tally.pieces,
+                    tally.wasted
+                )
+            })
+            .collect()
+    }
+}
+
+/// Row where `piece` lands if dropped from where it is.
+fn landing(game_map: &[Vec<u8>], piece: &Tetromino) -> usize {
+    let mut y = piece.y;
+    while piece.test_position(game_map, piece.current_state as usize, piece.x, y + 1) {
+        y += 1;
+    }
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetromino;
+
+    /// Least presses placing an O spawned on an empty board in column `x`.
+    fn least_for_o(shift: AutoShift, x: isize) -> u32 {
+        let game = Game::with_seed(0);
+        let piece = tetromino::from_kind(4).unwrap();
+        let landing = landing(&game.game_map, &piece);
+        movegen::least_presses(&game.game_map, &piece, shift)
+            [&movegen::cases(&piece, 0, x, landing)]
+    }
+
+    fn press(action: Action) -> Input {
+        Input {
+            action,
+            press: true,
+        }
+    }
+
+    #[test]
+    fn fast_shifts_only_stop_at_the_wall() {
+        let shift = AutoShift::default();
+        assert_eq!(least_for_o(shift, 5), 1);
+        assert_eq!(least_for_o(shift, 0), 2);
+        assert_eq!(least_for_o(shift, 1), 3);
+        assert_eq!(least_for_o(shift, 3), 3);
+        let instant = AutoShift { das: DAS, arr: 0 };
+        assert_eq!(least_for_o(instant, 1), 3);
+    }
+
+    #[test]
+    fn slow_shifts_stop_anywhere() {
+        let shift = AutoShift { das: DAS, arr: 10 };
+        assert_eq!(least_for_o(shift, 0), 2);
+        assert_eq!(least_for_o(shift, 1), 2);
+        assert_eq!(least_for_o(shift, 3), 2);
+    }
+
+    #[test]
+    fn tapping_to_the_wall_is_a_fault() {
+        let mut game = Game::with_seed(0);
+        let mut finesse = Finesse::new(AutoShift::default());
+        game.current_piece = tetromino::from_kind(4);
+        for _ in 0..5 {
+            finesse.apply_input(&mut game, press(Action::Left));
+        }
+        assert!(finesse
+            .apply_input(&mut game, press(Action::HardDrop))
+            .is_some());
+
+        game.current_piece = tetromino::from_kind(4);
+        finesse.spawned();
+        finesse.apply_input(&mut game, press(Action::Left));
+        for _ in 0..4 {
+            let held = Input {
+                action: Action::Left,
+                press: false,
+            };
+            finesse.apply_input(&mut game, held);
+        }
+        finesse.apply_input(&mut game, press(Action::HardDrop));
+
+        assert_eq!(finesse.pieces(), 2);
+        assert_eq!(finesse.faults(), 1);
+        assert_eq!(finesse.accuracy(), 50);
+        assert_eq!(finesse.tallies[&(4, 0)].wasted, 4);
+        assert_eq!(
+            finesse.last_fault,
+            Some(Fault {
+                kind: 4,
+                state: 0,
+                presses: 6,
+                least: 2,
+            })
+        );
+    }
+}
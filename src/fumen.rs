@@ -0,0 +1,671 @@
+//! Fumen strings, the way players share boards: pages of a field 10 cases wide with the piece
+//! played on each, in the v115 format of the fumen editor and of `tetris-fumen`.
+
+use crate::game::{Game, GARBAGE};
+use crate::tbp::{self, Location, Orientation, Piece};
+use crate::tetromino::{self, Tetromino};
+
+/// Rows of a fumen field, most of them above our board.
+pub const FIELD_HEIGHT: usize = 23;
+pub const FIELD_WIDTH: usize = 10;
+/// Cases of the field then of the garbage row under it.
+const FIELD_CASES: usize = (FIELD_HEIGHT + 1) * FIELD_WIDTH;
+/// Version of the format, following the letter telling which viewer the string is for.
+const VERSION: &str = "115@";
+/// Digits of the base 64 values strings are made of, lowest first.
+const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/// Characters of escaped comments, each a digit in base `COMMENT_BASE`.
+const COMMENT_CHARS: &[u8; 95] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE: u32 = 96;
+/// Longest comment once escaped.
+const MAX_COMMENT: usize = 4095;
+/// Value of a field case that did not change since the previous page.
+const SAME: u32 = 8;
+
+/// A page of a fumen.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    /// `FIELD_HEIGHT` rows from top to bottom, holding cases as our boards do.
+    pub field: Vec<Vec<u8>>,
+    /// Row under the field, pushed into it by pages that rise.
+    pub garbage: Vec<u8>,
+    pub piece: Option<Location>,
+    /// Whether the piece locks, and full rows clear, before the next page.
+    pub lock: bool,
+    /// Whether the garbage row rises into the field before the next page.
+    pub rise: bool,
+    /// Whether the field gets flipped left to right before the next page.
+    pub mirror: bool,
+    pub comment: String,
+}
+
+impl Page {
+    pub fn empty() -> Page {
+        Page {
+            field: vec![vec![0; FIELD_WIDTH]; FIELD_HEIGHT],
+            garbage: vec![0; FIELD_WIDTH],
+            piece: None,
+            lock: true,
+            rise: false,
+            mirror: false,
+            comment: String::new(),
+        }
+    }
+
+    /// The field once the piece of the page locked, as the next page starts with it.
+    pub fn locked_field(&self) -> Vec<Vec<u8>> {
+        self.next_field().0
+    }
+
+    /// The field and garbage row of the page following this one.
+    fn next_field(&self) -> (Vec<Vec<u8>>, Vec<u8>) {
+        let mut field = self.field.clone();
+        let mut garbage = self.garbage.clone();
+        if !self.lock {
+            return (field, garbage);
+        }
+        if let Some(ref location) = self.piece {
+            for (x, y) in tbp::location_cases(location) {
+                if x >= 0 && (x as usize) < FIELD_WIDTH && y >= 0 && (y as usize) < FIELD_HEIGHT {
+                    field[FIELD_HEIGHT - 1 - y as usize][x as usize] = location.piece.kind();
+                }
+            }
+        }
+        field.retain(|row| row.contains(&0));
+        while field.len() < FIELD_HEIGHT {
+            field.insert(0, vec![0; FIELD_WIDTH]);
+        }
+        if self.rise {
+            field.remove(0);
+            field.push(garbage);
+            garbage = vec![0; FIELD_WIDTH];
+        }
+        if self.mirror {
+            for row in field.iter_mut() {
+                row.reverse();
+            }
+        }
+        (field, garbage)
+    }
+}
+
+/// The rows of `field` from the highest one holding a case down, as text boards have them.
+pub fn trim(field: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let top = field
+        .iter()
+        .position(|row| row.iter().any(|case| *case != 0))
+        .unwrap_or_else(|| field.len().saturating_sub(1));
+    field[top..].to_vec()
+}
+
+/// The fumen of the board and current piece of `game`.
+pub fn export(game: &Game) -> String {
+    encode(&[page(game)])
+}
+
+/// A page showing the board of `game` at the bottom of the field, with its current piece.
+pub fn page(game: &Game) -> Page {
+    let mut page = Page::empty();
+    let rows = game.game_map.len().min(FIELD_HEIGHT);
+    let board = &game.game_map[game.game_map.len() - rows..];
+    for (row, board_row) in page.field[FIELD_HEIGHT - rows..].iter_mut().zip(board) {
+        for (case, board_case) in row.iter_mut().zip(board_row) {
+            *case = *board_case;
+        }
+    }
+    page.piece = game.current_piece.as_ref().and_then(|piece| {
+        let rows = game.game_map.len();
+        let cases = tbp::placement_cases(piece, piece.current_state, piece.x, piece.y, rows);
+        tbp::locate(Piece::from_kind(piece.kind())?, &cases)
+    });
+    page
+}
+
+/// Puts the field and piece of `page` on the board of `game`, which keeps its queue and hold.
+/// Fails if they don't fit on the board.
+pub fn load(page: &Page, game: &mut Game) -> Result<(), String> {
+    let rows = game.game_map.len();
+    if rows > FIELD_HEIGHT || game.game_map.iter().any(|row| row.len() != FIELD_WIDTH) {
+        return Err(format!(
+            "Fumen fields only fit boards of {} columns and at most {} rows",
+            FIELD_WIDTH, FIELD_HEIGHT
+        ));
+    }
+    if page.field[..FIELD_HEIGHT - rows]
+        .iter()
+        .any(|row| row.iter().any(|case| *case != 0))
+    {
+        return Err(format!(
+            "The field is higher than the {} rows of the board",
+            rows
+        ));
+    }
+    let game_map = page.field[FIELD_HEIGHT - rows..].to_vec();
+    let current_piece = match page.piece {
+        Some(ref location) => Some(tetromino_at(location, &game_map)?),
+        None => None,
+    };
+    game.game_map = game_map;
+    game.current_piece = current_piece;
+    Ok(())
+}
+
+/// Our piece covering the cases of `location` on `game_map`.
+fn tetromino_at(location: &Location, game_map: &[Vec<u8>]) -> Result<Tetromino, String> {
+    let rows = game_map.len();
+    let cases = tbp::location_cases(location);
+    let mut piece = tetromino::from_kind(location.piece.kind()).expect("Pieces have a kind");
+    for state in 0..piece.states.len() {
+        for y in 0..rows {
+            for x in -3..FIELD_WIDTH as isize {
+                if tbp::placement_cases(&piece, state as u8, x, y, rows) != cases {
+                    continue;
+                }
+                if !piece.test_position(game_map, state, x, y) {
+                    return Err("The piece overlaps the board".to_owned());
+                }
+                piece.current_state = state as u8;
+                piece.x = x;
+                piece.y = y;
+                return Ok(piece);
+            }
+        }
+    }
+    Err("The piece is not within the board".to_owned())
+}
+
+/// The fumen of `pages`.
+pub fn encode(pages: &[Page]) -> String {
+    let mut values = Vec::new();
+    let mut field = vec![vec![0; FIELD_WIDTH]; FIELD_HEIGHT];
+    let mut garbage = vec![0; FIELD_WIDTH];
+    // Where the count of the following pages that keep the field as it is was written.
+    let mut repeat: Option<usize> = None;
+    let mut comment = "";
+    for (index, page) in pages.iter().enumerate() {
+        let runs = field_runs(&field, &garbage, page);
+        let unchanged = runs == [(SAME, FIELD_CASES)];
+        match repeat {
+            Some(at) if unchanged && values[at] + 1 < DIGITS.len() as u32 => values[at] += 1,
+            _ => {
+                for (diff, count) in runs {
+                    push(&mut values, diff * FIELD_CASES as u32 + count as u32 - 1, 2);
+                }
+                repeat = if unchanged {
+                    values.push(0);
+                    Some(values.len() - 1)
+                } else {
+                    None
+                };
+            }
+        }
+
+        let with_comment = page.comment != comment;
+        push(&mut values, action(page, index == 0, with_comment), 3);
+        if with_comment {
+            push_comment(&mut values, &page.comment);
+            comment = &page.comment;
+        }
+        let (next, next_garbage) = page.next_field();
+        field = next;
+        garbage = next_garbage;
+    }
+    let data: String = values
+        .iter()
+        .map(|value| DIGITS[*value as usize] as char)
+        .collect();
+    format!("v{}{}", VERSION, data)
+}
+
+/// The pages of `data`, a fumen string or a link ending with one.
+pub fn decode(data: &str) -> Result<Vec<Page>, String> {
+    let start = data
+        .find(VERSION)
+        .filter(|at| *at > 0 && b"vmd".contains(&data.as_bytes()[at - 1]))
+        .ok_or("Not a fumen of version 115")?;
+    let mut reader = Reader {
+        values: data[start + VERSION.len()..]
+            .bytes()
+            .filter(|byte| *byte != b'?')
+            .map(
+                |byte| match DIGITS.iter().position(|digit| *digit == byte) {
+                    Some(value) => Ok(value as u32),
+                    None => Err(format!("Invalid character in the fumen: {}", byte as char)),
+                },
+            )
+            .collect::<Result<_, _>>()?,
+        next: 0,
+    };
+
+    let mut pages = Vec::new();
+    let mut field = vec![vec![0; FIELD_WIDTH]; FIELD_HEIGHT];
+    let mut garbage = vec![0; FIELD_WIDTH];
+    let mut repeat = 0;
+    let mut comment = String::new();
+    while reader.next < reader.values.len() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut index = 0;
+            while index < FIELD_CASES {
+                let run = reader.poll(2)?;
+                let diff = run / FIELD_CASES as u32;
+                let count = (run % FIELD_CASES as u32) as usize + 1;
+                if index + count > FIELD_CASES {
+                    return Err("Invalid field in the fumen".to_owned());
+                }
+                if diff == SAME && count == FIELD_CASES {
+                    repeat = reader.poll(1)?;
+                }
+                for case in index..index + count {
+                    let cell = match case / FIELD_WIDTH {
+                        FIELD_HEIGHT => &mut garbage[case % FIELD_WIDTH],
+                        row => &mut field[row][case % FIELD_WIDTH],
+                    };
+                    let value = (fumen_value(*cell) + diff)
+                        .checked_sub(SAME)
+                        .filter(|value| *value <= 8)
+                        .ok_or("Invalid field in the fumen")?;
+                    *cell = case_of(value);
+                }
+                index += count;
+            }
+        }
+
+        let mut action = reader.poll(3)?;
+        let kind = action % 8;
+        action /= 8;
+        let rotation = action % 4;
+        action /= 4;
+        let position = action % FIELD_CASES as u32;
+        action /= FIELD_CASES as u32;
+        let flag = |bit: u32| action >> bit & 1 == 1;
+        let with_comment = flag(3);
+        if with_comment {
+            comment = read_comment(&mut reader)?;
+        }
+        let page = Page {
+            field: field.clone(),
+            garbage: garbage.clone(),
+            piece: piece_of(kind).map(|piece| location(piece, rotation, position)),
+            lock: !flag(4),
+            rise: flag(0),
+            mirror: flag(1),
+            comment: comment.clone(),
+        };
+        let (next, next_garbage) = page.next_field();
+        field = next;
+        garbage = next_garbage;
+        pages.push(page);
+    }
+    if pages.is_empty() {
+        return Err("The fumen has no page".to_owned());
+    }
+    Ok(pages)
+}
+
+/// Values of a fumen being read.
+struct Reader {
+    values: Vec<u32>,
+    next: usize,
+}
+
+impl Reader {
+    /// The next value written with `digits` digits.
+    fn poll(&mut self, digits: usize) -> Result<u32, String> {
+        let values = self
+            .values
+            .get(self.next..self.next + digits)
+            .ok_or("The fumen ends too early")?;
+        self.next += digits;
+        Ok(values
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 64 + digit))
+    }
+}
+
+/// Writes `value` with `digits` digits, the lowest first.
+fn push(values: &mut Vec<u32>, mut value: u32, digits: usize) {
+    for _ in 0..digits {
+        values.push(value % 64);
+        value /= 64;
+    }
+}
+
+/// Runs of cases of `page` changing the same way since the previous field, as the difference
+/// of their values plus `SAME` and the length of the run.
+fn field_runs(field: &[Vec<u8>], garbage: &[u8], page: &Page) -> Vec<(u32, usize)> {
+    let before = field.iter().flatten().chain(garbage);
+    let after = page.field.iter().flatten().chain(&page.garbage);
+    let mut runs: Vec<(u32, usize)> = Vec::new();
+    for (before, after) in before.zip(after) {
+        let diff = fumen_value(*after) + SAME - fumen_value(*before);
+        match runs.last_mut() {
+            Some((last, count)) if *last == diff => *count += 1,
+            _ => runs.push((diff, 1)),
+        }
+    }
+    runs
+}
+
+/// The value of the action of `page`, `first` telling whether it is the first page.
+fn action(page: &Page, first: bool, with_comment: bool) -> u32 {
+    let (kind, rotation, position) = match page.piece {
+        Some(ref location) => (
+            piece_value(location.piece),
+            rotation_value(location.orientation),
+            position_value(location),
+        ),
+        None => (0, 0, 0),
+    };
+    // Only the first page asks for the pieces to get their colors.
+    let flags = page.rise as u32
+        | (page.mirror as u32) << 1
+        | (first as u32) << 2
+        | (with_comment as u32) << 3
+        | (!page.lock as u32) << 4;
+    kind + 8 * (rotation + 4 * (position + FIELD_CASES as u32 * flags))
+}
+
+/// How far the center of `piece` is from the case fumen positions it with.
+fn center_shift(piece: Piece, orientation: Orientation) -> (i32, i32) {
+    match (piece, orientation) {
+        (Piece::O, Orientation::North) => (0, -1),
+        (Piece::O, Orientation::South) => (1, 0),
+        (Piece::O, Orientation::West) => (1, -1),
+        (Piece::I, Orientation::South) => (1, 0),
+        (Piece::I, Orientation::West) => (0, -1),
+        (Piece::S, Orientation::North) => (0, -1),
+        (Piece::S, Orientation::East) => (-1, 0),
+        (Piece::Z, Orientation::North) => (0, -1),
+        (Piece::Z, Orientation::West) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+/// The case the piece of `location` is positioned with, counting from the top left of the
+/// field. Pieces must be within the field.
+fn position_value(location: &Location) -> u32 {
+    let (dx, dy) = center_shift(location.piece, location.orientation);
+    let x = (location.x - dx).clamp(0, FIELD_WIDTH as i32 - 1);
+    let y = (location.y - dy).clamp(-1, FIELD_HEIGHT as i32 - 1);
+    (FIELD_HEIGHT as i32 - 1 - y) as u32 * FIELD_WIDTH as u32 + x as u32
+}
+
+fn location(piece: Piece, rotation: u32, position: u32) -> Location {
+    let orientation = match rotation {
+        0 => Orientation::South,
+        1 => Orientation::East,
+        2 => Orientation::North,
+        _ => Orientation::West,
+    };
+    let (dx, dy) = center_shift(piece, orientation);
+    let position = position as i32;
+    Location {
+        piece,
+        orientation,
+        x: position % FIELD_WIDTH as i32 + dx,
+        y: FIELD_HEIGHT as i32 - 1 - position / FIELD_WIDTH as i32 + dy,
+    }
+}
+
+fn rotation_value(orientation: Orientation) -> u32 {
+    match orientation {
+        Orientation::South => 0,
+        Orientation::East => 1,
+        Orientation::North => 2,
+        Orientation::West => 3,
+    }
+}
+
+/// Pieces in the order of their fumen values, from 1.
+const PIECES: [Piece; 7] = [
+    Piece::I,
+    Piece::L,
+    Piece::O,
+    Piece::Z,
+    Piece::T,
+    Piece::J,
+    Piece::S,
+];
+
+fn piece_value(piece: Piece) -> u32 {
+    PIECES
+        .iter()
+        .position(|other| *other == piece)
+        .map_or(0, |index| index as u32 + 1)
+}
+
+/// The piece of a fumen value, `None` for empty and garbage cases.
+fn piece_of(value: u32) -> Option<Piece> {
+    PIECES.get((value as usize).checked_sub(1)?).cloned()
+}
+
+/// The fumen value of a case of our boards.
+fn fumen_value(case: u8) -> u32 {
+    match case {
+        0 => 0,
+        GARBAGE => 8,
+        kind => Piece::from_kind(kind).map_or(8, piece_value),
+    }
+}
+
+/// The case of our boards for a fumen value.
+fn case_of(value: u32) -> u8 {
+    match value {
+        0 => 0,
+        value => piece_of(value).map_or(GARBAGE, Piece::kind),
+    }
+}
+
+fn push_comment(values: &mut Vec<u32>, comment: &str) {
+    let escaped = escape(comment);
+    let escaped = &escaped.as_bytes()[..escaped.len().min(MAX_COMMENT)];
+    push(values, escaped.len() as u32, 2);
+    for chunk in escaped.chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, byte| {
+            let digit = COMMENT_CHARS
+                .iter()
+                .position(|char| char == byte)
+                .expect("Escaped comments are printable ASCII");
+            value * COMMENT_BASE + digit as u32
+        });
+        push(values, value, 5);
+    }
+}
+
+fn read_comment(reader: &mut Reader) -> Result<String, String> {
+    let length = reader.poll(2)? as usize;
+    let mut escaped = String::new();
+    for _ in 0..length.div_ceil(4) {
+        let mut value = reader.poll(5)?;
+        for _ in 0..4 {
+            let char = COMMENT_CHARS
+                .get((value % COMMENT_BASE) as usize)
+                .ok_or("Invalid comment in the fumen")?;
+            escaped.push(*char as char);
+            value /= COMMENT_BASE;
+        }
+    }
+    escaped.truncate(length);
+    Ok(unescape(&escaped))
+}
+
+/// `text` escaped as by `escape` in JavaScript, which the fumen editor does to comments.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for unit in text.encode_utf16() {
+        match unit {
+            0..=0x7f
+                if (unit as u8).is_ascii_alphanumeric() || b"@*_+-./".contains(&(unit as u8)) =>
+            {
+                escaped.push(unit as u8 as char)
+            }
+            0..=0xff => escaped.push_str(&format!("%{:02X}", unit)),
+            _ => escaped.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    escaped
+}
+
+/// The text `escape` turned into `escaped`.
+fn unescape(escaped: &str) -> String {
+    let hex = |digits: Option<&str>| {
+        digits
+            .filter(|digits| digits.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+    };
+    let mut units = Vec::new();
+    let mut at = 0;
+    while at < escaped.len() {
+        if escaped.as_bytes()[at] == b'%' {
+            if escaped.as_bytes().get(at + 1) == Some(&b'u') {
+                if let Some(unit) = hex(escaped.get(at + 2..at + 6)) {
+                    units.push(unit);
+                    at += 6;
+                    continue;
+                }
+            } else if let Some(unit) = hex(escaped.get(at + 1..at + 3)) {
+                units.push(unit);
+                at += 3;
+                continue;
+            }
+        }
+        units.push(escaped.as_bytes()[at] as u16);
+        at += 1;
+    }
+    String::from_utf16_lossy(&units)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+
+    fn piece(piece: Piece, orientation: Orientation, x: i32, y: i32) -> Location {
+        Location {
+            piece,
+            orientation,
+            x,
+            y,
+        }
+    }
+
+    #[test]
+    fn an_empty_page_matches_the_editor() {
+        assert_eq!(encode(&[Page::empty()]), "v115@vhAAgH");
+        assert_eq!(decode("v115@vhAAgH").unwrap(), vec![Page::empty()]);
+        assert_eq!(
+            decode("https://fumen.zui.jp/?v115@vhAAgH").unwrap(),
+            vec![Page::empty()]
+        );
+    }
+
+    /// A vertical I dropped into a well, clearing the garbage row, then an S standing
+    /// upright: two of the pieces fumen positions away from their center.
+    #[test]
+    fn decodes_pieces_off_their_spawn_rotation() {
+        let data = "v115@bhI8Ke5IJvhAPqB";
+        let pages = decode(data).unwrap();
+        assert_eq!(pages.len(), 2);
+        let mut well = vec![GARBAGE; FIELD_WIDTH];
+        well[9] = 0;
+        assert_eq!(pages[0].field[FIELD_HEIGHT - 1], well);
+
+        let i = piece(Piece::I, Orientation::West, 9, 1);
+        assert_eq!(pages[0].piece, Some(i));
+        assert_eq!(tbp::location_cases(&i), [(9, 0), (9, 1), (9, 2), (9, 3)]);
+        let s = piece(Piece::S, Orientation::East, 1, 1);
+        assert_eq!(pages[1].piece, Some(s));
+        assert_eq!(tbp::location_cases(&s), [(1, 1), (1, 2), (2, 0), (2, 1)]);
+        assert_eq!(pages[1].field, pages[0].locked_field());
+
+        let mut game = Game::with_seed(0);
+        load(&pages[0], &mut game).unwrap();
+        let current = game.current_piece.unwrap();
+        let rows = game.game_map.len();
+        assert_eq!(
+            tbp::placement_cases(&current, current.current_state, current.x, current.y, rows),
+            tbp::location_cases(&i)
+        );
+
+        assert_eq!(encode(&pages), data);
+    }
+
+    #[test]
+    fn decode_reads_back_what_encode_wrote() {
+        let mut first = Page::empty();
+        first.field[FIELD_HEIGHT - 1] = vec![GARBAGE, GARBAGE, GARBAGE, 0, 1, 1, 1, 1, 0, 0];
+        first.garbage = vec![GARBAGE; FIELD_WIDTH];
+        first.garbage[4] = 0;
+        first.piece = Some(piece(Piece::T, Orientation::North, 1, 1));
+        first.comment = "T first".to_owned();
+
+        let mut pages = vec![first];
+        for (location, comment) in [
+            (piece(Piece::O, Orientation::North, 8, 1), "T first"),
+            (piece(Piece::J, Orientation::East, 3, 1), "Then J"),
+            (piece(Piece::L, Orientation::West, 8, 3), "Then J"),
+        ] {
+            let mut page = Page::empty();
+            page.field = pages.last().unwrap().locked_field();
+            page.piece = Some(location);
+            page.comment = comment.to_owned();
+            pages.push(page);
+        }
+        pages[1].garbage = pages[0].garbage.clone();
+        pages[1].rise = true;
+        pages[2].lock = false;
+        pages[3].mirror = true;
+        pages[3].field = pages[2].locked_field();
+
+        let mut unchanged = Page::empty();
+        unchanged.field = pages[3].locked_field();
+        unchanged.comment = "Done".to_owned();
+        pages.push(unchanged.clone());
+        pages.push(unchanged);
+
+        assert_eq!(decode(&encode(&pages)).unwrap(), pages);
+    }
+
+    #[test]
+    fn decode_rejects_broken_fumens() {
+        assert!(decode("v114@vhAAgH").is_err());
+        assert!(decode("v115@").is_err());
+        assert!(decode("v115@vh!AgH").is_err());
+        assert!(decode("v115@vhA").is_err());
+    }
+
+    #[test]
+    fn load_puts_back_what_export_wrote() {
+        let mut game = Game::with_seed(0);
+        let rows = board::parse("....OO....\nGGG.OOGGGG").unwrap();
+        board::load(&rows, &mut game).unwrap();
+        let mut current = game.create_new_tetromino();
+        current.current_state = current.states.len() as u8 - 1;
+        current.x = 1;
+        current.y = 4;
+        game.current_piece = Some(current.clone());
+
+        let mut loaded = Game::with_seed(1);
+        let pages = decode(&export(&game)).unwrap();
+        load(&pages[0], &mut loaded).unwrap();
+        assert_eq!(loaded.game_map, game.game_map);
+        let piece = loaded.current_piece.unwrap();
+        let rows = game.game_map.len();
+        assert_eq!(piece.kind(), current.kind());
+        assert_eq!(
+            tbp::placement_cases(&piece, piece.current_state, piece.x, piece.y, rows),
+            tbp::placement_cases(&current, current.current_state, current.x, current.y, rows)
+        );
+    }
+
+    #[test]
+    fn load_rejects_fields_higher_than_the_board() {
+        let mut page = Page::empty();
+        page.field[0][0] = GARBAGE;
+        assert!(load(&page, &mut Game::with_seed(0)).is_err());
+    }
+}
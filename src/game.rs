@@ -1,31 +1,145 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
 use std::time::SystemTime;
 
-use crate::tetromino::{self, Tetromino, TetrominoGenerator};
+use crate::tetromino::{self, Tetromino};
 
-pub const LEVEL_TIMES: [u32; 10] = [1000, 850, 700, 600, 500, 400, 300, 250, 221, 190];
-pub const LEVEL_LINES: [u32; 10] = [20, 40, 60, 80, 100, 120, 140, 160, 180, 200];
+pub const LEVEL_TIMES: [u32; 15] = [
+    1000, 850, 700, 600, 500, 400, 300, 250, 221, 190, 160, 130, 100, 80, 60,
+];
+pub const LINES_PER_LEVEL: u32 = 10;
+/// Cell value used for garbage blocks, after the seven piece colors.
+pub const GARBAGE: u8 = 8;
+/// Number of upcoming pieces known in advance.
+pub const PREVIEW_SIZE: usize = 5;
+/// Mixed into the seed of a game to seed its garbage.
+const GARBAGE_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
 
+/// Everything a player can do with the current piece.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    Rotate,
+    HardDrop,
+    /// Swaps the current piece with the held one, once per piece.
+    Hold,
+}
+
+/// Where the hole of a garbage row goes, from the hole of the row below it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HoleRule {
+    /// Chance that the hole moves to another column.
+    pub change: f64,
+    /// How far it moves when it does, from 0 for a neighbouring column to 1 for anywhere on
+    /// the row.
+    pub messiness: f64,
+}
+
+impl HoleRule {
+    /// A new hole anywhere on the row.
+    pub const RANDOM: HoleRule = HoleRule {
+        change: 1.0,
+        messiness: 1.0,
+    };
+    /// The hole of the previous row.
+    pub const SAME: HoleRule = HoleRule {
+        change: 0.0,
+        messiness: 0.0,
+    };
+}
+
+/// What happened when a piece got locked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineClear {
+    pub lines: u32,
+    pub t_spin: bool,
+    pub all_clear: bool,
+    /// Number of line clears in a row before this one.
+    pub combo: u32,
+    /// Whether this is a tetris or T-spin clear following another one.
+    pub back_to_back: bool,
+}
+
+impl LineClear {
+    /// Tetrises and T-spin clears keep the back-to-back chain going.
+    pub fn is_difficult(&self) -> bool {
+        self.lines == 4 || (self.t_spin && self.lines > 0)
+    }
+}
+
+#[derive(Clone)]
 pub struct Game {
     pub game_map: Vec<Vec<u8>>,
     pub current_level: u32,
     pub score: u32,
     pub num_lines: u32,
+    pub garbage_lines: u32,
     pub current_piece: Option<Tetromino>,
+    /// Next pieces, in order.
+    pub queue: VecDeque<Tetromino>,
+    pub hold: Option<Tetromino>,
+    /// Whether the current piece can still be held.
+    pub can_hold: bool,
+    pub seed: u64,
+    /// Number of consecutive locks that cleared lines.
+    pub combo: u32,
+    /// Whether the last line clear was a tetris or a T-spin.
+    pub back_to_back: bool,
+    rng: StdRng,
+    /// Draws the garbage holes, apart from `rng` so that garbage doesn't change the pieces.
+    garbage_rng: StdRng,
+    prev_piece: u8,
+    garbage_hole: usize,
+    last_move_rotation: bool,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Game {
+        Game::with_seed(rand::random())
+    }
+
+    /// Creates a game whose pieces and garbage are fully determined by `seed`.
+    pub fn with_seed(seed: u64) -> Game {
         let mut game_map = Vec::new();
         for _ in 0..16 {
             game_map.push(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         }
-        Game {
+        let rng = StdRng::seed_from_u64(seed);
+        let mut garbage_rng = StdRng::seed_from_u64(seed ^ GARBAGE_SEED);
+        let garbage_hole = garbage_rng.gen_range(0, 10);
+        let mut game = Game {
             game_map,
             current_level: 1,
             score: 0,
             num_lines: 0,
+            garbage_lines: 0,
             current_piece: None,
+            queue: VecDeque::new(),
+            hold: None,
+            can_hold: true,
+            seed,
+            combo: 0,
+            back_to_back: false,
+            rng,
+            garbage_rng,
+            prev_piece: 7,
+            garbage_hole,
+            last_move_rotation: false,
+        };
+        for _ in 0..PREVIEW_SIZE {
+            let piece = game.random_tetromino();
+            game.queue.push_back(piece);
         }
+        game
     }
 
     pub fn update_score(&mut self, points: u32) {
@@ -38,35 +152,67 @@ impl Game {
 
     pub fn increase_line(&mut self) {
         self.num_lines += 1;
-        if self.num_lines > LEVEL_LINES[self.current_level as usize - 1] {
+        if self.num_lines.is_multiple_of(LINES_PER_LEVEL) {
             self.increase_level();
         }
     }
 
-    pub fn create_new_tetromino(&self) -> Tetromino {
-        static mut PREV: u8 = 7;
-        let mut rand_n = rand::random::<u8>() % 7;
-        if unsafe { PREV } == rand_n {
-            rand_n = rand::random::<u8>() % 7;
+    /// Takes the next piece of the queue.
+    pub fn create_new_tetromino(&mut self) -> Tetromino {
+        let piece = self.random_tetromino();
+        self.queue.push_back(piece);
+        self.queue
+            .pop_front()
+            .expect("The piece queue is never empty")
+    }
+
+    /// Replaces the queue with pieces of `kinds`, followed by random pieces while there are
+    /// fewer than `PREVIEW_SIZE`.
+    pub fn set_queue(&mut self, kinds: &[u8]) {
+        self.queue = kinds
+            .iter()
+            .filter_map(|kind| tetromino::from_kind(*kind))
+            .collect();
+        while self.queue.len() < PREVIEW_SIZE {
+            let piece = self.random_tetromino();
+            self.queue.push_back(piece);
         }
-        unsafe {
-            PREV = rand_n;
+    }
+
+    fn random_tetromino(&mut self) -> Tetromino {
+        let mut rand_n = self.rng.gen_range(0, 7);
+        if self.prev_piece == rand_n {
+            rand_n = self.rng.gen_range(0, 7);
         }
-        match rand_n {
-            0 => tetromino::TetrominoI::new(),
-            1 => tetromino::TetrominoJ::new(),
-            2 => tetromino::TetrominoL::new(),
-            3 => tetromino::TetrominoO::new(),
-            4 => tetromino::TetrominoS::new(),
-            5 => tetromino::TetrominoZ::new(),
-            6 => tetromino::TetrominoT::new(),
-            _ => unreachable!(),
+        self.prev_piece = rand_n;
+        tetromino::from_kind(rand_n + 1).expect("Piece kinds go from 1 to 7")
+    }
+
+    /// Puts the current piece on hold, taking the held one back or the next one if there was
+    /// none. Returns false if holding is not allowed until the next lock.
+    pub fn hold_piece(&mut self) -> bool {
+        if !self.can_hold {
+            return false;
         }
+        let current = match self.current_piece.take() {
+            Some(piece) => piece,
+            None => return false,
+        };
+        self.current_piece = match self.hold.take() {
+            Some(held) => Some(held),
+            None => Some(self.create_new_tetromino()),
+        };
+        self.hold = tetromino::from_kind(current.kind());
+        self.can_hold = false;
+        self.last_move_rotation = false;
+        true
     }
 
-    pub fn check_lines(&mut self) {
+    /// Removes the complete lines and returns how many there were.
+    pub fn check_lines(&mut self) -> u32 {
         let mut y = 0;
         let mut points_to_add = 0;
+        let mut lines = 0;
 
         while y < self.game_map.len() {
             let mut complete = true;
@@ -78,11 +224,15 @@ impl Game {
                 }
             }
             if complete == true {
+                lines += 1;
                 points_to_add += self.current_level;
+                if self.game_map[y].contains(&GARBAGE) {
+                    self.garbage_lines += 1;
+                }
                 self.game_map.remove(y);
-                y -= 1;
+            } else {
+                y += 1;
             }
-            y += 1;
         }
         if self.game_map.len() == 0 {
             points_to_add += 1000;
@@ -92,9 +242,130 @@ impl Game {
             self.increase_line();
             self.game_map.insert(0, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         }
+        lines
+    }
+
+    /// Builds a full garbage row with a single hole, placed from the hole of the previous row
+    /// by `holes`.
+    pub fn generate_garbage_row(&mut self, holes: HoleRule) -> Vec<u8> {
+        let width = self.game_map[0].len();
+        if self.garbage_rng.gen_bool(holes.change.clamp(0.0, 1.0)) {
+            // Columns within reach on either side of the hole, the hole itself left out.
+            let reach = 1 + (holes.messiness.clamp(0.0, 1.0) * (width - 2) as f64).round() as usize;
+            let first = self.garbage_hole.saturating_sub(reach);
+            let last = (self.garbage_hole + reach).min(width - 1);
+            let column = self.garbage_rng.gen_range(first, last);
+            self.garbage_hole = if column >= self.garbage_hole {
+                column + 1
+            } else {
+                column
+            };
+        }
+        let mut row = vec![GARBAGE; width];
+        row[self.garbage_hole] = 0;
+        row
+    }
+
+    /// Pushes `count` garbage rows in from the bottom of the board, moving everything up.
+    pub fn insert_garbage_rows(&mut self, count: usize, holes: HoleRule) -> bool {
+        for _ in 0..count {
+            let row = self.generate_garbage_row(holes);
+            if !self.insert_row(row) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Inserts `row` at the bottom of the board and shifts the stack up by one. The active
+    /// piece is pushed up as well if it would overlap the stack. Returns false on top-out,
+    /// i.e. when blocks are pushed out of the board or the piece has no room left.
+    pub fn insert_row(&mut self, row: Vec<u8>) -> bool {
+        let removed = self.game_map.remove(0);
+        self.game_map.push(row);
+        if removed.iter().any(|case| *case != 0) {
+            return false;
+        }
+        if let Some(ref mut piece) = self.current_piece {
+            while !piece.test_current_position(&self.game_map) {
+                if piece.y == 0 {
+                    return false;
+                }
+                piece.y -= 1;
+            }
+        }
+        true
     }
 
-    pub fn make_permanent(&mut self) {
+    pub fn clear_board(&mut self) {
+        for row in self.game_map.iter_mut() {
+            for case in row.iter_mut() {
+                *case = 0;
+            }
+        }
+    }
+
+    /// Number of rows that still contain garbage.
+    pub fn garbage_rows(&self) -> usize {
+        self.game_map
+            .iter()
+            .filter(|row| row.contains(&GARBAGE))
+            .count()
+    }
+
+    /// Applies `action` to the current piece. Returns what the lock did if the piece got
+    /// locked.
+    pub fn apply_action(&mut self, action: Action) -> Option<LineClear> {
+        if action == Action::Hold {
+            self.hold_piece();
+            return None;
+        }
+        let mut make_permanent = false;
+        if let Some(ref mut piece) = self.current_piece {
+            let x = piece.x;
+            let y = piece.y;
+            let moved = match action {
+                Action::Left => piece.change_position(&self.game_map, x - 1, y),
+                Action::Right => piece.change_position(&self.game_map, x + 1, y),
+                Action::Rotate => {
+                    if piece.rotate(&self.game_map) {
+                        self.last_move_rotation = true;
+                    }
+                    false
+                }
+                Action::SoftDrop => {
+                    let moved = piece.change_position(&self.game_map, x, y + 1);
+                    make_permanent = !moved;
+                    moved
+                }
+                Action::HardDrop => {
+                    while piece.change_position(&self.game_map, x, piece.y + 1) {}
+                    make_permanent = true;
+                    piece.y != y
+                }
+                Action::Hold => false,
+            };
+            if moved {
+                self.last_move_rotation = false;
+            }
+        }
+        if make_permanent {
+            Some(self.make_permanent())
+        } else {
+            None
+        }
+    }
+
+    /// A T piece whose last move was a rotation, see `t_spin_corners`.
+    fn is_t_spin(&self, piece: &Tetromino) -> bool {
+        piece.is_t() && self.last_move_rotation && t_spin_corners(&self.game_map, piece)
+    }
+
+    pub fn make_permanent(&mut self) -> LineClear {
+        let t_spin = match self.current_piece {
+            Some(ref piece) => self.is_t_spin(piece),
+            None => false,
+        };
         if let Some(ref mut piece) = self.current_piece {
             let mut shift_y = 0;
 
@@ -117,8 +388,75 @@ impl Game {
                 shift_y += 1;
             }
         }
-        self.check_lines();
+        let lines = self.check_lines();
         self.current_piece = None;
+        self.can_hold = true;
+        self.last_move_rotation = false;
+
+        let mut clear = LineClear {
+            lines,
+            t_spin,
+            all_clear: false,
+            combo: 0,
+            back_to_back: false,
+        };
+        if lines > 0 {
+            clear.all_clear = self
+                .game_map
+                .iter()
+                .all(|row| row.iter().all(|case| *case == 0));
+            clear.combo = self.combo;
+            clear.back_to_back = self.back_to_back && clear.is_difficult();
+            self.combo += 1;
+            self.back_to_back = clear.is_difficult();
+        } else {
+            self.combo = 0;
+        }
+        clear
+    }
+}
+
+/// Three-corner rule: whether at least three of the four cases diagonal to the center of the
+/// T `piece` are filled (walls and floor count). Its last move must also have been a rotation
+/// for the lock to be a T-spin.
+pub fn t_spin_corners(game_map: &[Vec<u8>], piece: &Tetromino) -> bool {
+    let state = &piece.states[piece.current_state as usize];
+    let filled = |y: usize, x: usize| y < state.len() && x < state[y].len() && state[y][x] != 0;
+    // The center is the only case of a T with three neighbours.
+    for (y, line) in state.iter().enumerate() {
+        for (x, case) in line.iter().enumerate() {
+            if *case == 0 {
+                continue;
+            }
+            let neighbours = [
+                y > 0 && filled(y - 1, x),
+                filled(y + 1, x),
+                x > 0 && filled(y, x - 1),
+                filled(y, x + 1),
+            ];
+            if neighbours.iter().filter(|n| **n).count() < 3 {
+                continue;
+            }
+            let center_x = piece.x + x as isize;
+            let center_y = piece.y as isize + y as isize;
+            let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+                .iter()
+                .filter(|(dx, dy)| is_case_blocked(game_map, center_x + dx, center_y + dy))
+                .count();
+            return corners >= 3;
+        }
+    }
+    false
+}
+
+/// Walls and floor are blocked, the area above the board is not.
+fn is_case_blocked(game_map: &[Vec<u8>], x: isize, y: isize) -> bool {
+    if x < 0 || x as usize >= game_map[0].len() || y >= game_map.len() as isize {
+        true
+    } else if y < 0 {
+        false
+    } else {
+        game_map[y as usize][x as usize] != 0
     }
 }
 
@@ -126,8 +464,78 @@ pub fn is_time_over(game: &Game, timer: &SystemTime) -> bool {
     match timer.elapsed() {
         Ok(elapsed) => {
             let milliseconds = elapsed.as_secs() as u32 * 1000 + elapsed.subsec_nanos() / 1_000_000;
-            milliseconds > LEVEL_TIMES[game.current_level as usize + 1]
+            milliseconds > level_time(game.current_level)
         }
         Err(_) => false,
     }
 }
+
+/// Gravity delay in frames at 60 frames per second, for fixed-step simulations.
+pub fn level_frames(level: u32) -> u32 {
+    (level_time(level) * 60 / 1000).max(1)
+}
+
+/// Gravity delay in milliseconds; levels past the end of `LEVEL_TIMES` keep the fastest speed.
+pub fn level_time(level: u32) -> u32 {
+    let index = (level.max(1) as usize - 1).min(LEVEL_TIMES.len() - 1);
+    LEVEL_TIMES[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hole(row: &[u8]) -> usize {
+        row.iter().position(|case| *case == 0).unwrap()
+    }
+
+    fn kinds(game: &mut Game, count: usize) -> Vec<u8> {
+        (0..count)
+            .map(|_| game.create_new_tetromino().kind())
+            .collect()
+    }
+
+    #[test]
+    fn garbage_leaves_the_pieces_alone() {
+        let mut clean = Game::with_seed(9);
+        let mut dirty = Game::with_seed(9);
+        assert_eq!(kinds(&mut clean, 10), kinds(&mut dirty, 10));
+        assert!(crate::attack::insert_garbage(&mut dirty, 4));
+        dirty.insert_garbage_rows(2, HoleRule::RANDOM);
+        assert_eq!(kinds(&mut clean, 50), kinds(&mut dirty, 50));
+    }
+
+    #[test]
+    fn complete_top_rows_are_cleared() {
+        let mut game = Game::with_seed(0);
+        game.game_map[0] = vec![GARBAGE; 10];
+        game.game_map[1] = vec![GARBAGE; 10];
+        assert_eq!(game.check_lines(), 2);
+        assert_eq!(game.garbage_lines, 2);
+        assert!(game.game_map.iter().flatten().all(|case| *case == 0));
+    }
+
+    #[test]
+    fn holes_stay_without_hole_change() {
+        let mut game = Game::with_seed(3);
+        let first = hole(&game.generate_garbage_row(HoleRule::RANDOM));
+        for _ in 0..20 {
+            assert_eq!(hole(&game.generate_garbage_row(HoleRule::SAME)), first);
+        }
+    }
+
+    #[test]
+    fn tidy_holes_move_to_a_neighbouring_column() {
+        let mut game = Game::with_seed(5);
+        let tidy = HoleRule {
+            change: 1.0,
+            messiness: 0.0,
+        };
+        let mut previous = hole(&game.generate_garbage_row(tidy));
+        for _ in 0..50 {
+            let next = hole(&game.generate_garbage_row(tidy));
+            assert_eq!((next as isize - previous as isize).abs(), 1);
+            previous = next;
+        }
+    }
+}
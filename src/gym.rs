@@ -0,0 +1,475 @@
+//! Reinforcement learning environment in the style of Gym: `Env::reset` starts a game and
+//! returns what the agent sees, `Env::step` plays an action and returns what came of it.
+//! `serve` drives it with JSON messages, one per line, so trainers in other languages can use
+//! it through the standard input and output of `tetrs-gym`.
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::attack::AttackTable;
+use crate::game::Action;
+use crate::mode::{Mode, ModeArgs};
+use crate::movegen::{self, Placement};
+use crate::sim::{Ending, Simulation};
+use crate::tbp::Piece;
+use crate::tetromino::{self, Tetromino};
+
+/// Actions of the key action space, `None` letting a frame pass without doing anything.
+pub const KEY_ACTIONS: [Option<Action>; 7] = [
+    None,
+    Some(Action::Left),
+    Some(Action::Right),
+    Some(Action::Rotate),
+    Some(Action::SoftDrop),
+    Some(Action::HardDrop),
+    Some(Action::Hold),
+];
+/// Episodes are cut short after this many frames, ten minutes of play.
+pub const MAX_FRAMES: u32 = 10 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionSpace {
+    /// An action is an index in `KEY_ACTIONS`, played on a frame of its own.
+    Keys,
+    /// An action is an index in the placements of the observation, all of it played on one
+    /// frame.
+    #[default]
+    Placements,
+}
+
+/// What the reward of a step counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reward {
+    #[default]
+    Lines,
+    Score,
+    /// Garbage lines the clears would send in versus.
+    Attack,
+}
+
+/// The current piece, where it is.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ActivePiece {
+    pub piece: Piece,
+    pub state: u8,
+    pub x: isize,
+    pub y: usize,
+    /// The cases of the piece, as the rows of the board.
+    pub rows: Vec<u16>,
+}
+
+/// A choice of the placement action space.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Choice {
+    pub piece: Piece,
+    /// Whether the current piece is held first, `piece` being the held or the next one.
+    pub hold: bool,
+    pub state: u8,
+    pub x: isize,
+    pub y: usize,
+    pub t_spin: bool,
+}
+
+/// Everything the agent knows about the game.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Observation {
+    /// Rows from top to bottom, bit `x` being set when the case of column `x` is filled.
+    pub board: Vec<u16>,
+    pub piece: Option<ActivePiece>,
+    pub queue: Vec<Piece>,
+    pub hold: Option<Piece>,
+    pub can_hold: bool,
+    pub combo: u32,
+    pub back_to_back: bool,
+    /// Choices of the placement action space, empty in the key action space.
+    pub placements: Vec<Choice>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Info {
+    pub lines: u32,
+    pub score: u32,
+    pub level: u32,
+    pub pieces: u32,
+    pub frames: u32,
+    /// Lines cleared by the step.
+    pub cleared: u32,
+    /// Garbage lines the step would send in versus.
+    pub attack: u32,
+    pub ending: Option<Ending>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    pub info: Info,
+}
+
+pub struct Env {
+    pub space: ActionSpace,
+    pub reward: Reward,
+    pub max_frames: u32,
+    mode: Mode,
+    simulation: Simulation,
+    /// Placements of the choices of the last observation, hold included.
+    placements: Vec<Placement>,
+    table: AttackTable,
+}
+
+impl Env {
+    /// An environment playing games of `mode`, ready to play one with seed 0.
+    pub fn new(mode: Mode, space: ActionSpace, reward: Reward) -> Env {
+        Env {
+            space,
+            reward,
+            max_frames: MAX_FRAMES,
+            mode,
+            simulation: Simulation::new(mode, 0),
+            placements: Vec::new(),
+            table: AttackTable::default(),
+        }
+    }
+
+    /// Starts a new game whose pieces and garbage are determined by `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.simulation = Simulation::new(self.mode, seed);
+        self.observe()
+    }
+
+    /// Plays `action` of the action space. Fails if there is no such action.
+    pub fn step(&mut self, action: usize) -> Result<Step, String> {
+        let score = self.simulation.game.score;
+        let clears = match self.space {
+            ActionSpace::Keys => {
+                let key = KEY_ACTIONS
+                    .get(action)
+                    .ok_or_else(|| format!("No key action {}", action))?;
+                self.simulation
+                    .frame(&key.iter().cloned().collect::<Vec<_>>())
+            }
+            ActionSpace::Placements if self.simulation.ending.is_some() => Vec::new(),
+            ActionSpace::Placements => {
+                let placement = self
+                    .placements
+                    .get(action)
+                    .ok_or_else(|| format!("No placement {}", action))?;
+                let actions = placement.actions.clone();
+                self.simulation.frame(&actions)
+            }
+        };
+        if self.simulation.ending.is_none() && self.simulation.frames >= self.max_frames {
+            self.simulation.ending = Some(Ending::FrameLimit);
+        }
+
+        let game = &self.simulation.game;
+        let cleared = clears.iter().map(|clear| clear.lines).sum();
+        let attack = clears.iter().map(|clear| self.table.attack(clear)).sum();
+        let reward = match self.reward {
+            Reward::Lines => cleared,
+            Reward::Score => game.score - score,
+            Reward::Attack => attack,
+        };
+        let info = Info {
+            lines: game.num_lines,
+            score: game.score,
+            level: game.current_level,
+            pieces: self.simulation.pieces,
+            frames: self.simulation.frames,
+            cleared,
+            attack,
+            ending: self.simulation.ending,
+        };
+        Ok(Step {
+            observation: self.observe(),
+            reward: reward as f64,
+            done: self.simulation.ending.is_some(),
+            info,
+        })
+    }
+
+    fn observe(&mut self) -> Observation {
+        let game = &self.simulation.game;
+        let rows = game.game_map.len();
+        self.placements.clear();
+        let mut choices = Vec::new();
+        if self.space == ActionSpace::Placements && self.simulation.ending.is_none() {
+            if let Some(ref current) = game.current_piece {
+                let mut pieces = vec![(current.clone(), false)];
+                let next = match game.hold {
+                    Some(ref held) => Some(held),
+                    None => game.queue.front(),
+                };
+                if let Some(next) = next.filter(|_| game.can_hold) {
+                    if next.kind() != current.kind() {
+                        pieces.push((spawn(next), true));
+                    }
+                }
+                for (piece, hold) in pieces {
+                    for mut placement in movegen::placements(&game.game_map, &piece) {
+                        choices.push(Choice {
+                            piece: piece_of(&piece),
+                            hold,
+                            state: placement.state,
+                            x: placement.x,
+                            y: placement.y,
+                            t_spin: placement.t_spin,
+                        });
+                        if hold {
+                            placement.actions.insert(0, Action::Hold);
+                        }
+                        self.placements.push(placement);
+                    }
+                }
+            }
+        }
+        Observation {
+            board: game
+                .game_map
+                .iter()
+                .map(|row| bits(row.iter().map(|case| *case != 0)))
+                .collect(),
+            piece: game.current_piece.as_ref().map(|piece| ActivePiece {
+                piece: piece_of(piece),
+                state: piece.current_state,
+                x: piece.x,
+                y: piece.y,
+                rows: piece_rows(piece, rows),
+            }),
+            queue: game.queue.iter().map(piece_of).collect(),
+            hold: game.hold.as_ref().map(piece_of),
+            can_hold: game.can_hold,
+            combo: game.combo,
+            back_to_back: game.back_to_back,
+            placements: choices,
+        }
+    }
+}
+
+fn piece_of(piece: &Tetromino) -> Piece {
+    Piece::from_kind(piece.kind()).expect("Piece kinds go from 1 to 7")
+}
+
+/// A new piece of the kind of `piece`, where pieces spawn.
+fn spawn(piece: &Tetromino) -> Tetromino {
+    tetromino::from_kind(piece.kind()).expect("Piece kinds go from 1 to 7")
+}
+
+/// `cases` as a bitmask, the first one being the lowest bit.
+fn bits<I: Iterator<Item = bool>>(cases: I) -> u16 {
+    cases
+        .enumerate()
+        .filter(|(_, filled)| *filled)
+        .fold(0, |mask, (x, _)| mask | 1 << x)
+}
+
+/// The cases of `piece` on a board of `rows` rows.
+fn piece_rows(piece: &Tetromino, rows: usize) -> Vec<u16> {
+    let mut mask = vec![0; rows];
+    let state = &piece.states[piece.current_state as usize];
+    for (line_num, line) in state.iter().enumerate() {
+        let y = piece.y + line_num;
+        if y >= rows {
+            break;
+        }
+        for (x_num, case) in line.iter().enumerate() {
+            let x = piece.x + x_num as isize;
+            if *case != 0 && x >= 0 {
+                mask[y] |= 1 << x;
+            }
+        }
+    }
+    mask
+}
+
+/// Messages read by `serve`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Starts a game. `mode` takes the mode flags of `tetrs`, marathon by default.
+    Reset {
+        seed: u64,
+        #[serde(default)]
+        mode: Vec<String>,
+        #[serde(default)]
+        action_space: ActionSpace,
+        #[serde(default)]
+        reward: Reward,
+        max_frames: Option<u32>,
+    },
+    Step {
+        action: usize,
+    },
+    Close {},
+}
+
+/// Messages written by `serve`, one for every request but `close`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Response {
+    Reset { observation: Observation },
+    Step(Step),
+    Error { error: String },
+}
+
+/// Runs environments for the requests read from `input`, until it ends or asks to close.
+pub fn serve<R: BufRead, W: Write>(input: R, mut output: W) -> io::Result<()> {
+    let mut env: Option<Env> = None;
+    for line in input.lines() {
+        let response = match serde_json::from_str(&line?) {
+            Ok(Request::Reset {
+                seed,
+                mode,
+                action_space,
+                reward,
+                max_frames,
+            }) => match parse_mode(mode) {
+                Ok(mode) => {
+                    let env = env.insert(Env::new(mode, action_space, reward));
+                    env.max_frames = max_frames.unwrap_or(MAX_FRAMES);
+                    Response::Reset {
+                        observation: env.reset(seed),
+                    }
+                }
+                Err(error) => Response::Error { error },
+            },
+            Ok(Request::Step { action }) => match env {
+                Some(ref mut env) => match env.step(action) {
+                    Ok(step) => Response::Step(step),
+                    Err(error) => Response::Error { error },
+                },
+                None => Response::Error {
+                    error: "Step before any reset".to_owned(),
+                },
+            },
+            Ok(Request::Close {}) => break,
+            Err(e) => Response::Error {
+                error: e.to_string(),
+            },
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn parse_mode(flags: Vec<String>) -> Result<Mode, String> {
+    let mut mode = ModeArgs::default();
+    let mut flags = flags.into_iter();
+    while let Some(flag) = flags.next() {
+        if !mode.parse(&flag, &mut flags)? {
+            return Err(format!("Unknown mode flag: {}", flag));
+        }
+    }
+    Ok(mode.mode())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(space: ActionSpace) -> Env {
+        Env::new(Mode::marathon(150, false), space, Reward::Lines)
+    }
+
+    #[test]
+    fn reset_is_deterministic() {
+        let mut env = env(ActionSpace::Placements);
+        let first = env.reset(7);
+        env.step(0).unwrap();
+        assert_eq!(env.reset(7), first);
+        assert_eq!(Env::new(env.mode, env.space, env.reward).reset(7), first);
+    }
+
+    #[test]
+    fn placement_actions_lock_that_placement() {
+        let mut env = env(ActionSpace::Placements);
+        let observation = env.reset(3);
+        let piece = env.simulation.game.current_piece.clone().unwrap();
+        let (action, choice) = observation
+            .placements
+            .iter()
+            .enumerate()
+            .rfind(|(_, choice)| !choice.hold)
+            .unwrap();
+        let mut board = observation.board.clone();
+        for (y, x) in movegen::cases(&piece, choice.state, choice.x, choice.y) {
+            board[y] |= 1 << x;
+        }
+
+        let step = env.step(action).unwrap();
+        assert_eq!(step.observation.board, board);
+        assert_eq!(step.info.pieces, 2);
+        assert!(!step.done);
+    }
+
+    #[test]
+    fn key_actions_play_their_action() {
+        let mut env = env(ActionSpace::Keys);
+        let x = env.reset(3).piece.unwrap().x;
+        assert!(env.reset(3).placements.is_empty());
+
+        let moved = |step: Step| step.observation.piece.unwrap().x;
+        assert_eq!(moved(env.step(0).unwrap()), x);
+        assert_eq!(moved(env.step(1).unwrap()), x - 1);
+        assert_eq!(moved(env.step(2).unwrap()), x);
+        assert_eq!(env.step(5).unwrap().info.pieces, 2);
+        assert!(env.step(KEY_ACTIONS.len()).is_err());
+    }
+
+    #[test]
+    fn topping_out_ends_the_episode() {
+        let mut env = env(ActionSpace::Placements);
+        let mut observation = env.reset(5);
+        for _ in 0..200 {
+            // Stacks every piece as high as it goes.
+            let action = (0..observation.placements.len())
+                .min_by_key(|&index| observation.placements[index].y)
+                .unwrap();
+            let step = env.step(action).unwrap();
+            if step.done {
+                assert_eq!(step.info.ending, Some(Ending::ToppedOut));
+                assert!(step.observation.placements.is_empty());
+                return;
+            }
+            observation = step.observation;
+        }
+        panic!("The stack never topped out");
+    }
+
+    #[test]
+    fn serve_answers_every_request_until_closed() {
+        let input = concat!(
+            r#"{"type": "reset", "seed": 9}"#,
+            "\n",
+            r#"{"type": "step", "action": 0}"#,
+            "\n",
+            r#"{"type": "close"}"#,
+            "\n",
+            r#"{"type": "step", "action": 0}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let mut env = Env::new(
+            parse_mode(Vec::new()).unwrap(),
+            ActionSpace::Placements,
+            Reward::Lines,
+        );
+        let expected = [
+            serde_json::to_string(&Response::Reset {
+                observation: env.reset(9),
+            })
+            .unwrap(),
+            serde_json::to_string(&Response::Step(env.step(0).unwrap())).unwrap(),
+        ];
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,60 @@
+//! Game engine shared by the `tetrs` game, the `tetrs-server` match server, the `tetrs-sim`
+//! simulator and the `tetrs-gym` environment. Nothing in here depends on SDL.
+
+extern crate rand;
+extern crate serde;
+extern crate serde_json;
+
+pub mod attack;
+pub mod battle;
+pub mod board;
+pub mod bot;
+pub mod files;
+pub mod finesse;
+pub mod fumen;
+pub mod game;
+pub mod gym;
+pub mod mode;
+pub mod movegen;
+pub mod net;
+pub mod opener;
+pub mod perfect_clear;
+pub mod puzzle;
+pub mod rollback;
+pub mod search;
+pub mod server;
+pub mod sim;
+pub mod spectate;
+pub mod tbp;
+pub mod tetromino;
+pub mod tournament;
+
+use std::ops::Range;
+
+/// Parses the value following `flag` on the command line.
+pub fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    match value {
+        Some(value) => value
+            .parse::<T>()
+            .map_err(|_| format!("Invalid value for {}: {}", flag, value)),
+        None => Err(format!("Missing value for {}", flag)),
+    }
+}
+
+/// The `count` seeds from `first`, failing if they go past the last one.
+pub fn seed_range(first: u64, count: u64) -> Result<Range<u64>, String> {
+    match first.checked_add(count) {
+        Some(end) => Ok(first..end),
+        None => Err(format!(
+            "{} seeds from seed {} run out of seeds",
+            count, first
+        )),
+    }
+}
+
+/// `seconds` given with `flag`, in frames of play.
+pub fn seconds_to_frames(flag: &str, seconds: u32) -> Result<u32, String> {
+    seconds
+        .checked_mul(sim::FPS)
+        .ok_or_else(|| format!("Invalid value for {}: {}", flag, seconds))
+}
@@ -1,27 +1,76 @@
 extern crate rand;
 extern crate sdl2;
+extern crate tetrs;
 
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture, TextureCreator};
-use sdl2::video::{Window, WindowContext};
+use sdl2::render::TextureCreator;
 
+use std::env;
 use std::fs::File;
 use std::io::{self, Read, Write};
-use std::thread::sleep;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, sleep};
 use std::time::{Duration, SystemTime};
 
+use options::Options;
+use render::Renderer;
+use tetrs::bot::{Bot, Strategy};
+use tetrs::mode::Mode;
+use tetrs::perfect_clear::{self, PC_LINES};
+use tetrs::search::Search;
+use tetrs::spectate::Broadcaster;
+use tetrs::tbp::{self, ExternalBot};
+use tetrs::{attack, battle, board, bot, finesse, fumen, game, mode, net, rollback, tetromino};
+
+mod editor;
 mod events;
-mod game;
-mod tetromino;
+mod openers;
+mod options;
+mod puzzles;
+mod render;
+mod trainer;
+mod versus;
+mod watch;
 
-const GAME_HEIGHT: usize = 40;
-const HIGHSCORE_FILE: &'static str = "scores.txt";
 const NUM_HIGHSCORES: usize = 5;
+/// Ten seconds of play.
+const LATENCY_TEST_FRAMES: u32 = 600;
+/// Pieces between two garbage rows in the bot protocol test.
+const TBP_TEST_GARBAGE_INTERVAL: u32 = 10;
+/// Items shown at once in menus.
+const MENU_SIZE: usize = 10;
+
+/// Board, current piece, held piece and whether it can be held: everything a perfect clear
+/// search depends on but the queue, which only changes with them.
+type HintKey = (Vec<Vec<u8>>, Option<u8>, Option<u8>, bool);
 
 fn main() {
-    let mut tetrs = game::Game::new();
-    let mut timer = SystemTime::now();
+    let options = match Options::from_args(env::args()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", options::usage());
+            return;
+        }
+    };
+    if let Some(latency) = options.latency_test {
+        run_latency_test(latency, &options);
+        return;
+    }
+    if options.tbp_bot {
+        let stdin = io::stdin();
+        if let Err(e) = tbp::serve(&built_in_bot(&options), stdin.lock(), io::stdout()) {
+            eprintln!("{}", e);
+        }
+        return;
+    }
+    if let Some(pieces) = options.tbp_test {
+        run_tbp_test(pieces, &options);
+        return;
+    }
     let sdl_ctx = sdl2::init().expect("Failed to initialize SDL");
     let ttf_ctx = sdl2::ttf::init().expect("SDL TTF init failed");
     let video_subsystem = sdl_ctx
@@ -37,16 +86,13 @@ fn main() {
 
     let mut event_pump = sdl_ctx.event_pump().expect("Failed to get SDL event pump");
 
-    let grid_x = (width - GAME_HEIGHT as u32 * 10) as i32 / 2;
-    let grid_y = (height - GAME_HEIGHT as u32 * 16) as i32 / 2;
-
     let window = video_subsystem
         .window("Tetrs", width, height)
         .position_centered()
         .build()
         .expect("Failed to create window");
 
-    let mut canvas = window
+    let canvas = window
         .into_canvas()
         .target_texture()
         .present_vsync()
@@ -54,244 +100,466 @@ fn main() {
         .expect("Failed to create canvas");
 
     let texture_creator: TextureCreator<_> = canvas.texture_creator();
+    let mut renderer = Renderer::new(canvas, &texture_creator, &font, width, height);
+
+    if let Some(ref addr) = options.watch {
+        watch::run_watch(&mut renderer, &mut event_pump, addr);
+    } else if options.host.is_some() || options.connect.is_some() || options.server.is_some() {
+        versus::run_network(&mut renderer, &mut event_pump, &options);
+    } else if options.editor {
+        match new_single_game(&options) {
+            Ok(tetrs) => editor::run_editor(&mut renderer, &mut event_pump, &options, tetrs),
+            Err(e) => eprintln!("{}", e),
+        }
+    } else if let Some(ref dir) = options.puzzles {
+        puzzles::run_puzzles(&mut renderer, &mut event_pump, dir);
+    } else if let Some(ref dir) = options.openers {
+        openers::run_openers(&mut renderer, &mut event_pump, dir);
+    } else if options.finesse {
+        trainer::run_finesse(&mut renderer, &mut event_pump, &options);
+    } else if options.versus {
+        versus::run_versus(&mut renderer, &mut event_pump, &options);
+    } else {
+        run_single(&mut renderer, &mut event_pump, &options);
+    }
+}
 
-    let grid = create_texture_rect(
-        &mut canvas,
-        &texture_creator,
-        0,
-        0,
-        0,
-        GAME_HEIGHT as u32 * 10,
-        GAME_HEIGHT as u32 * 16,
-    )
-    .expect("Failed to create grid");
-
-    let border = create_texture_rect(
-        &mut canvas,
-        &texture_creator,
-        255,
-        255,
-        255,
-        GAME_HEIGHT as u32 * 10 + 20,
-        GAME_HEIGHT as u32 * 16 + 20,
-    )
-    .expect("Failed to create border");
-
-    macro_rules! texture {
-        ($r:expr, $g:expr, $b:expr) => {
-            create_texture_rect(
-                &mut canvas,
-                &texture_creator,
-                $r,
-                $g,
-                $b,
-                GAME_HEIGHT as u32,
-                GAME_HEIGHT as u32,
-            )
-            .unwrap()
-        };
+/// Runs two rollback peers over a simulated connection and checks that they stay in sync.
+fn run_latency_test(latency: u64, options: &Options) {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let settings = net::Settings {
+        seeds: [seed, seed.wrapping_add(1)],
+        garbage_delay: options.garbage_delay,
+        input_delay: options.input_delay,
+    };
+    println!(
+        "Simulating {} frames with {}ms latency and {}ms jitter (seed {})",
+        LATENCY_TEST_FRAMES, latency, options.jitter, seed
+    );
+    match rollback::latency_test(
+        Duration::from_millis(latency),
+        Duration::from_millis(options.jitter),
+        settings,
+        LATENCY_TEST_FRAMES,
+    ) {
+        Ok(rollbacks) => println!("In sync, {} and {} rollbacks", rollbacks[0], rollbacks[1]),
+        Err(e) => println!("Failed: {}", e),
     }
+}
 
-    let textures = [
-        texture!(255, 69, 69),
-        texture!(255, 220, 69),
-        texture!(237, 150, 37),
-        texture!(171, 99, 237),
-        texture!(77, 149, 239),
-        texture!(39, 218, 225),
-        texture!(45, 216, 47),
-    ];
+/// Plays a headless game with the external bot, or with this game acting as one, and checks
+/// that every move it suggests can be played.
+fn run_tbp_test(pieces: u32, options: &Options) {
+    let command = match options.tbp {
+        Some(ref command) => command.clone(),
+        None => match env::current_exe() {
+            Ok(path) => format!("{} --tbp-bot", path.display()),
+            Err(e) => {
+                println!("Failed: {}", e);
+                return;
+            }
+        },
+    };
+    let seed = options.seed.unwrap_or_else(rand::random);
+    println!("Playing {} pieces with {} (seed {})", pieces, command, seed);
+    match tbp::self_test(&command, seed, pieces, TBP_TEST_GARBAGE_INTERVAL) {
+        Ok(game) => println!(
+            "Every move could be played: {} lines, score {}",
+            game.num_lines, game.score
+        ),
+        Err(e) => println!("Failed: {}", e),
+    }
+}
+
+/// A new single player game of `options.mode`, starting from the board of `options.fumen` or
+/// `options.board` if either is set.
+fn new_single_game(options: &Options) -> Result<game::Game, String> {
+    let mut tetrs = match options.seed {
+        Some(seed) => game::Game::with_seed(seed),
+        None => game::Game::new(),
+    };
+    options.mode.setup(&mut tetrs);
+    if let Some(ref page) = options.fumen {
+        fumen::load(page, &mut tetrs).map_err(|e| format!("Failed to load the fumen: {}", e))?;
+    }
+    if let Some(ref rows) = options.board {
+        board::load(rows, &mut tetrs).map_err(|e| format!("Failed to load the board: {}", e))?;
+    }
+    Ok(tetrs)
+}
+
+fn run_single(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, options: &Options) {
+    match new_single_game(options) {
+        Ok(tetrs) => play_single(renderer, event_pump, options, tetrs),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+/// Plays `tetrs`, set up for `options.mode`, until it ends or the player quits.
+pub fn play_single(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    options: &Options,
+    mut tetrs: game::Game,
+) {
+    let mut mode = options.mode;
+    let mut timer = SystemTime::now();
+    let mut broadcaster = broadcaster(options);
+    let mut bot = new_bot(options);
+
+    let start = SystemTime::now();
+    let mut mode_state = mode::ModeState::new(&mode);
+    let mut pc_hint = PcHint::default();
+    if tetrs.current_piece.is_some() {
+        mode.after_spawn(&tetrs, &mut mode_state);
+    }
 
     loop {
-        if game::is_time_over(&tetrs, &timer) {
-            let mut make_permanent = false;
-            if let Some(ref mut piece) = tetrs.current_piece {
-                let x = piece.x;
-                let y = piece.y + 1;
-                make_permanent = !piece.change_position(&tetrs.game_map, x, y);
+        if !mode.update(&mut tetrs, &mut mode_state, &start) {
+            print_game_info(&tetrs, &mode, mode.clock(&start));
+            break;
+        }
+        if mode.is_over(&start) {
+            print_game_info(&tetrs, &mode, mode.clock(&start));
+            break;
+        }
+        if mode.is_complete(&tetrs) {
+            let clock = mode.clock(&start);
+            let mut lines = vec![format!("{} COMPLETE", mode.name())];
+            if let Some(clock) = clock {
+                lines.push(format!("TIME: {}", mode::format_time(clock)));
             }
-            if make_permanent {
-                tetrs.make_permanent();
+            lines.push(format!("SCORE: {}", tetrs.score));
+            if mode.can_continue() {
+                lines.push("ENTER: KEEP PLAYING".to_owned());
             }
+            lines.push("ESCAPE: QUIT".to_owned());
+            if display_end_screen(renderer, event_pump, &lines) && mode.can_continue() {
+                mode = mode.endless();
+                timer = SystemTime::now();
+            } else {
+                print_game_info(&tetrs, &mode, clock);
+                break;
+            }
+        }
+        if mode.has_gravity() && game::is_time_over(&tetrs, &timer) {
+            tetrs.apply_action(game::Action::SoftDrop);
             timer = SystemTime::now();
         }
 
-        canvas.set_draw_color(Color::RGB(255, 0, 0));
-        canvas.clear();
-        canvas
-            .copy(
-                &border,
-                None,
-                Rect::new(
-                    (width - GAME_HEIGHT as u32 * 10) as i32 / 2 - 10,
-                    (height - GAME_HEIGHT as u32 * 16) as i32 / 2 - 10,
-                    GAME_HEIGHT as u32 * 10 + 20,
-                    GAME_HEIGHT as u32 * 16 + 20,
-                ),
-            )
-            .expect("Couldn't copy texture into window");
-        canvas
-            .copy(
-                &grid,
-                None,
-                Rect::new(
-                    (width - GAME_HEIGHT as u32 * 10) as i32 / 2,
-                    (height - GAME_HEIGHT as u32 * 16) as i32 / 2,
-                    GAME_HEIGHT as u32 * 10,
-                    GAME_HEIGHT as u32 * 16,
-                ),
-            )
-            .expect("Couldn't copy texture into window");
-
         if tetrs.current_piece.is_none() {
+            if !mode.before_spawn(&mut tetrs) {
+                print_game_info(&tetrs, &mode, mode.clock(&start));
+                break;
+            }
             let current_piece = tetrs.create_new_tetromino();
             if !current_piece.test_current_position(&tetrs.game_map) {
-                print_game_info(&tetrs);
-                break;
+                if mode.can_top_out() {
+                    print_game_info(&tetrs, &mode, mode.clock(&start));
+                    break;
+                }
+                tetrs.clear_board();
             }
             tetrs.current_piece = Some(current_piece);
+            mode.after_spawn(&tetrs, &mut mode_state);
         }
         let mut quit = false;
-
-        if !events::handle_events(&mut tetrs, &mut quit, &mut timer, &mut event_pump) {
-            if let Some(ref mut piece) = tetrs.current_piece {
-                for (line_num, line) in piece.states[piece.current_state as usize]
-                    .iter()
-                    .enumerate()
-                {
-                    for (case_num, case) in line.iter().enumerate() {
-                        if *case == 0 {
-                            continue;
-                        }
-                        canvas
-                            .copy(
-                                &textures[*case as usize - 1],
-                                None,
-                                Rect::new(
-                                    grid_x
-                                        + (piece.x + case_num as isize) as i32 * GAME_HEIGHT as i32,
-                                    grid_y + (piece.y + line_num) as i32 * GAME_HEIGHT as i32,
-                                    GAME_HEIGHT as u32,
-                                    GAME_HEIGHT as u32,
-                                ),
-                            )
-                            .expect("Couldn't copy texture to canvas");
-                    }
-                }
+        let mut undo = false;
+        let mut export = false;
+
+        match bot {
+            Some(ref mut bot) => {
+                events::poll_actions(event_pump, &[], &mut quit, &mut undo, &mut export);
+                let actions = bot.next_actions(&tetrs);
+                events::apply_actions(&mut tetrs, &actions, &mut timer);
             }
+            None => {
+                events::handle_events(
+                    &mut tetrs,
+                    &mut quit,
+                    &mut undo,
+                    &mut export,
+                    &mut timer,
+                    event_pump,
+                );
+            }
+        }
+
+        if undo && mode.undo(&mut tetrs, &mut mode_state) {
+            timer = SystemTime::now();
+        }
+        if export {
+            print_fumens(&[&tetrs]);
         }
 
         if quit {
-            print_game_info(&tetrs);
+            print_game_info(&tetrs, &mode, mode.clock(&start));
             break;
         }
 
-        for (line_num, line) in tetrs.game_map.iter().enumerate() {
-            for (case_num, case) in line.iter().enumerate() {
-                if *case == 0 {
-                    continue;
-                }
-                canvas
-                    .copy(
-                        &textures[*case as usize - 1],
-                        None,
-                        Rect::new(
-                            grid_x + case_num as i32 * GAME_HEIGHT as i32,
-                            grid_y + line_num as i32 * GAME_HEIGHT as i32,
-                            GAME_HEIGHT as u32,
-                            GAME_HEIGHT as u32,
-                        ),
-                    )
-                    .expect("Couldn't copy texture to canvas");
-            }
+        if options.pc_hint {
+            pc_hint.update(renderer, &tetrs);
+        }
+        let texts = game_info(&tetrs, &mode, &start);
+        renderer.draw_boards(&[(&tetrs, &texts)]);
+        if let Some(ref mut broadcaster) = broadcaster {
+            broadcaster.publish(&[(&tetrs, &texts)]);
         }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Perfect clear searches for the hint, run on a worker thread so that the game doesn't wait
+/// for them.
+#[derive(Default)]
+struct PcHint {
+    /// What the last search was started for.
+    searched: Option<HintKey>,
+    /// Where the result of the search in progress arrives.
+    result: Option<Receiver<Option<Vec<Vec<u8>>>>>,
+}
 
-        display_game_info(
-            &tetrs,
-            &mut canvas,
-            &texture_creator,
-            &font,
-            width as i32 - grid_x + 15,
+impl PcHint {
+    /// Starts a new search when the board or the pieces changed since the last one, and
+    /// shows the result of the search in progress once it is done.
+    fn update(&mut self, renderer: &mut Renderer, game: &game::Game) {
+        let key = (
+            game.game_map.clone(),
+            game.current_piece.as_ref().map(tetromino::Tetromino::kind),
+            game.hold.as_ref().map(tetromino::Tetromino::kind),
+            game.can_hold,
         );
+        if self.searched.as_ref() != Some(&key) {
+            // The hint of the previous position doesn't apply anymore.
+            renderer.hint = None;
+            let (sender, receiver) = mpsc::channel();
+            let game = game.clone();
+            thread::spawn(move || {
+                let board = perfect_clear::find(
+                    &game,
+                    PC_LINES,
+                    Duration::from_millis(perfect_clear::TIME_BUDGET),
+                )
+                .map(|solution| solution.board);
+                // Nobody is waiting anymore if the game moved on meanwhile.
+                let _ = sender.send(board);
+            });
+            self.searched = Some(key);
+            self.result = Some(receiver);
+        }
+        if let Some(board) = self
+            .result
+            .as_ref()
+            .and_then(|result| result.try_recv().ok())
+        {
+            renderer.hint = board;
+            self.result = None;
+        }
+    }
+}
 
-        canvas.present();
+/// Prints the fumen of every board of `games`, one per line.
+pub fn print_fumens(games: &[&game::Game]) {
+    for (player, game) in games.iter().enumerate() {
+        if games.len() > 1 {
+            println!("Player {}: {}", player + 1, fumen::export(game));
+        } else {
+            println!("{}", fumen::export(game));
+        }
+    }
+}
+
+/// The bot asked for with `options.bot` or `options.tbp`, if any.
+pub fn new_bot(options: &Options) -> Option<Bot> {
+    let command = match options.tbp {
+        Some(ref command) => command,
+        None if options.bot => return Some(built_in_bot(options)),
+        None => return None,
+    };
+    match ExternalBot::launch(command) {
+        Ok(external) => {
+            println!("{} {} is playing", external.name, external.version);
+            let mut bot = Bot::new(options.bot_delay);
+            bot.strategy = Strategy::External(Arc::new(Mutex::new(external)));
+            Some(bot)
+        }
+        Err(e) => {
+            eprintln!("Failed to start {}: {}", command, e);
+            None
+        }
+    }
+}
+
+/// The greedy bot, or the beam search one if `options.beam` is set.
+fn built_in_bot(options: &Options) -> Bot {
+    let mut bot = Bot::new(options.bot_delay);
+    if let Some(width) = options.beam {
+        bot.strategy = Strategy::Beam(Search {
+            width,
+            time_budget: options.bot_time.map(Duration::from_millis),
+            ..Search::default()
+        });
+    }
+    bot
+}
+
+/// Lets spectators watch the game if `options.broadcast` is set.
+pub fn broadcaster(options: &Options) -> Option<Broadcaster> {
+    let addr = options.broadcast.as_ref()?;
+    match Broadcaster::bind(addr) {
+        Ok(broadcaster) => Some(broadcaster),
+        Err(e) => {
+            eprintln!("Cannot broadcast on {}: {}", addr, e);
+            None
+        }
+    }
+}
+
+fn game_info(tetrs: &game::Game, mode: &Mode, start: &SystemTime) -> Vec<String> {
+    let mut texts = vec![
+        format!("SCORE: {}", tetrs.score),
+        format!("LINES: {}", tetrs.num_lines),
+        format!("LEVEL: {}", tetrs.current_level),
+    ];
+    if let Some(clock) = mode.clock(start) {
+        texts.push(format!("TIME: {}", mode::format_time(clock)));
+    }
+    if let Mode::Dig { target, .. } = *mode {
+        texts.push(format!("GARBAGE: {}/{}", tetrs.garbage_lines, target));
+    }
+    texts
+}
+
+/// Shows `lines` centered on screen until the player chooses to continue (true) or quit (false).
+pub fn display_end_screen(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    lines: &[String],
+) -> bool {
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return false,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Return),
+                    ..
+                } => return true,
+                _ => {}
+            }
+        }
+
+        renderer.clear(Color::RGB(0, 0, 0));
+        renderer.draw_centered_lines(lines, 250, 50);
+        renderer.present();
         sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
 }
 
-fn create_texture_from_text<'a>(
-    texture_creator: &'a TextureCreator<WindowContext>,
-    font: &sdl2::ttf::Font,
-    text: &str,
-    r: u8,
-    g: u8,
-    b: u8,
-) -> Option<Texture<'a>> {
-    if let Ok(surface) = font.render(text).blended(Color::RGB(r, g, b)) {
-        texture_creator.create_texture_from_surface(&surface).ok()
-    } else {
-        None
+/// Lists `items` under `title`, Up and Down moving `selected`, until the player picks one
+/// (true) or quits (false).
+pub fn display_menu(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    title: &str,
+    items: &[String],
+    selected: &mut usize,
+) -> bool {
+    loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return false,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::Up if *selected > 0 => *selected -= 1,
+                    Keycode::Down if *selected + 1 < items.len() => *selected += 1,
+                    Keycode::Return if *selected < items.len() => return true,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let first = (*selected + 1).saturating_sub(MENU_SIZE);
+        let mut lines = vec![title.to_owned(), String::new()];
+        for (i, item) in items.iter().enumerate().skip(first).take(MENU_SIZE) {
+            let marker = if i == *selected { ">" } else { " " };
+            lines.push(format!("{} {}", marker, item));
+        }
+        renderer.clear(Color::RGB(0, 0, 0));
+        renderer.draw_centered_lines(&lines, 100, 50);
+        renderer.present();
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+pub fn print_game_info(game: &game::Game, mode: &Mode, clock: Option<Duration>) {
+    match (*mode, mode.highscore_file()) {
+        (Mode::Marathon { .. }, Some(highscore_file)) => {
+            print_marathon_info(game, mode.is_complete(game), highscore_file)
+        }
+        (Mode::Ultra { .. }, Some(highscore_file)) => print_ultra_info(game, highscore_file),
+        (Mode::Survival { .. }, _) => print_survival_info(game, clock),
+        (Mode::Zen { .. }, _) => print_zen_info(game),
+        _ => print_dig_info(game, mode, clock),
     }
 }
 
-fn get_rect_from_text(text: &str, x: i32, y: i32) -> Option<Rect> {
-    Some(Rect::new(x, y, text.len() as u32 * 20, 30))
+fn print_zen_info(game: &game::Game) {
+    println!("Number of lines: {}", game.num_lines);
+    println!("Score:           {}", game.score);
 }
 
-fn display_game_info<'a>(
-    tetrs: &game::Game,
-    canvas: &mut Canvas<Window>,
-    texture_creator: &'a TextureCreator<WindowContext>,
-    font: &sdl2::ttf::Font,
-    start_point: i32,
-) {
-    let score_text = format!("SCORE: {}", tetrs.score);
-    let lines_text = format!("LINES: {}", tetrs.num_lines);
-    let level_text = format!("LEVEL: {}", tetrs.current_level);
-
-    let score = create_texture_from_text(&texture_creator, &font, &score_text, 255, 255, 255)
-        .expect("Cannot render text");
-    let lines = create_texture_from_text(&texture_creator, &font, &lines_text, 255, 255, 255)
-        .expect("Cannot render text");
-    let level = create_texture_from_text(&texture_creator, &font, &level_text, 255, 255, 255)
-        .expect("Cannot render text");
-
-    canvas
-        .copy(
-            &score,
-            None,
-            get_rect_from_text(&score_text, start_point, 90),
-        )
-        .expect("couldn't opy text");
-    canvas
-        .copy(
-            &lines,
-            None,
-            get_rect_from_text(&score_text, start_point, 125),
-        )
-        .expect("couldn't opy text");
-    canvas
-        .copy(
-            &level,
-            None,
-            get_rect_from_text(&score_text, start_point, 160),
-        )
-        .expect("couldn't opy text");
+fn print_survival_info(game: &game::Game, clock: Option<Duration>) {
+    println!("Game over...");
+    if let Some(clock) = clock {
+        println!("Survived:        {}", mode::format_time(clock));
+    }
+    println!("Number of lines: {}", game.num_lines);
+    println!("Score:           {}", game.score);
+    println!("Seed:            {}", game.seed);
 }
 
-pub fn print_game_info(game: &game::Game) {
+fn print_dig_info(game: &game::Game, mode: &Mode, clock: Option<Duration>) {
+    if mode.is_complete(game) {
+        println!("Dig complete!");
+    } else {
+        println!("Game over...");
+    }
+    if let Some(clock) = clock {
+        println!("Time:            {}", mode::format_time(clock));
+    }
+    println!("Garbage lines:   {}", game.garbage_lines);
+    println!("Score:           {}", game.score);
+    println!("Seed:            {}", game.seed);
+}
+
+fn print_marathon_info(game: &game::Game, complete: bool, highscore_file: &str) {
     let mut new_highest_highscore = true;
     let mut new_highest_lines_sent = true;
-    if let Some((mut highscores, mut lines_sent)) = load_highscores_and_lines() {
+    if let Some((mut highscores, mut lines_sent)) = load_highscores_and_lines(highscore_file) {
         new_highest_highscore = update_vec(&mut highscores, game.score);
         new_highest_lines_sent = update_vec(&mut lines_sent, game.num_lines);
         if new_highest_highscore || new_highest_lines_sent {
-            save_highscore_and_lines(&highscores, &lines_sent);
+            save_highscore_and_lines(&highscores, &lines_sent, highscore_file);
         }
     } else {
-        save_highscore_and_lines(&[game.score], &[game.num_lines]);
+        save_highscore_and_lines(&[game.score], &[game.num_lines], highscore_file);
+    }
+    if complete {
+        println!("Marathon complete!");
+    } else {
+        println!("Game over...");
     }
-    println!("Game over...");
     println!(
         "Score:           {}{}",
         game.score,
@@ -313,6 +581,29 @@ pub fn print_game_info(game: &game::Game) {
     println!("Current level:   {}", game.current_level);
 }
 
+fn print_ultra_info(game: &game::Game, highscore_file: &str) {
+    let mut new_highscore = true;
+    if let Some(mut highscores) = load_highscores(highscore_file) {
+        new_highscore = update_vec(&mut highscores, game.score);
+        if new_highscore {
+            save_highscores(&highscores, highscore_file);
+        }
+    } else {
+        save_highscores(&[game.score], highscore_file);
+    }
+    println!("Time's up!");
+    println!(
+        "Score:           {}{}",
+        game.score,
+        if new_highscore {
+            " [NEW HIGHSCORE]"
+        } else {
+            ""
+        }
+    );
+    println!("Number of lines: {}", game.num_lines);
+}
+
 fn update_vec(v: &mut Vec<u32>, value: u32) -> bool {
     if v.len() < NUM_HIGHSCORES {
         v.push(value);
@@ -342,11 +633,11 @@ fn line_to_slice(line: &str) -> Vec<u32> {
         .collect()
 }
 
-fn load_highscores_and_lines() -> Option<(Vec<u32>, Vec<u32>)> {
-    if let Ok(content) = read_from_file(HIGHSCORE_FILE) {
+fn load_highscores_and_lines(filename: &str) -> Option<(Vec<u32>, Vec<u32>)> {
+    if let Ok(content) = read_from_file(filename) {
         let mut lines = content
             .splitn(2, "\n")
-            .map(|line| line_to_slice(line))
+            .map(line_to_slice)
             .collect::<Vec<_>>();
         if lines.len() == 2 {
             let (number_lines, highscores) = (lines.pop().unwrap(), lines.pop().unwrap());
@@ -359,10 +650,22 @@ fn load_highscores_and_lines() -> Option<(Vec<u32>, Vec<u32>)> {
     }
 }
 
-fn save_highscore_and_lines(highscores: &[u32], lines: &[u32]) -> bool {
+fn save_highscore_and_lines(highscores: &[u32], lines: &[u32], filename: &str) -> bool {
     let s_highscores = slice_to_string(highscores);
     let s_lines = slice_to_string(lines);
-    write_into_file(&format!("{}\n{}\n", s_highscores, s_lines), HIGHSCORE_FILE).is_ok()
+    write_into_file(&format!("{}\n{}\n", s_highscores, s_lines), filename).is_ok()
+}
+
+fn load_highscores(filename: &str) -> Option<Vec<u32>> {
+    if let Ok(content) = read_from_file(filename) {
+        content.lines().next().map(line_to_slice)
+    } else {
+        None
+    }
+}
+
+fn save_highscores(highscores: &[u32], filename: &str) -> bool {
+    write_into_file(&format!("{}\n", slice_to_string(highscores)), filename).is_ok()
 }
 
 fn write_into_file(content: &str, filename: &str) -> io::Result<()> {
@@ -376,25 +679,3 @@ fn read_from_file(filename: &str) -> io::Result<String> {
     f.read_to_string(&mut content)?;
     Ok(content)
 }
-
-fn create_texture_rect<'a>(
-    canvas: &mut Canvas<Window>,
-    texture_creator: &'a TextureCreator<WindowContext>,
-    r: u8,
-    g: u8,
-    b: u8,
-    width: u32,
-    height: u32,
-) -> Option<Texture<'a>> {
-    if let Ok(mut square_texture) = texture_creator.create_texture_target(None, width, height) {
-        canvas
-            .with_texture_canvas(&mut square_texture, |texture| {
-                texture.set_draw_color(Color::RGB(r, g, b));
-                texture.clear();
-            })
-            .expect("Failed to color a texture");
-        Some(square_texture)
-    } else {
-        None
-    }
-}
@@ -0,0 +1,393 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::game::{Game, HoleRule};
+use crate::parse_value;
+
+pub const ULTRA_TIME_LIMIT: u64 = 120;
+pub const MARATHON_LINE_CAP: u32 = 150;
+pub const DIG_TARGET_LINES: u32 = 18;
+pub const DIG_HOLES: HoleRule = HoleRule::RANDOM;
+pub const SURVIVAL_INTERVAL: u64 = 8;
+pub const SURVIVAL_HOLES: HoleRule = HoleRule {
+    change: 0.3,
+    messiness: 1.0,
+};
+
+/// Number of locks that can be undone in zen mode.
+const ZEN_UNDO_LEVELS: usize = 100;
+
+/// Every garbage row makes the next one arrive this much sooner in survival mode...
+const SURVIVAL_INTERVAL_STEP: Duration = Duration::from_millis(150);
+/// ...down to this minimum interval.
+const SURVIVAL_MIN_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// Maximum number of garbage rows on the board at once in dig mode.
+const DIG_VISIBLE_ROWS: usize = 10;
+
+const MARATHON_HIGHSCORE_FILE: &str = "scores.txt";
+const ULTRA_HIGHSCORE_FILE: &str = "ultra_scores.txt";
+
+enum ModeKind {
+    Marathon,
+    Ultra,
+    Dig,
+    Survival,
+    Zen,
+}
+
+/// The mode chosen with command line flags, shared by the game and the simulator.
+pub struct ModeArgs {
+    kind: ModeKind,
+    time_limit: u64,
+    line_cap: u32,
+    endless: bool,
+    target: u32,
+    messiness: Option<f64>,
+    hole_change: Option<f64>,
+    interval: u64,
+    gravity: bool,
+}
+
+impl Default for ModeArgs {
+    fn default() -> ModeArgs {
+        ModeArgs {
+            kind: ModeKind::Marathon,
+            time_limit: ULTRA_TIME_LIMIT,
+            line_cap: MARATHON_LINE_CAP,
+            endless: false,
+            target: DIG_TARGET_LINES,
+            messiness: None,
+            hole_change: None,
+            interval: SURVIVAL_INTERVAL,
+            gravity: true,
+        }
+    }
+}
+
+impl ModeArgs {
+    /// Takes `flag`, with its value from `args`, if it is about the mode. Returns whether it
+    /// was.
+    pub fn parse<I: Iterator<Item = String>>(
+        &mut self,
+        flag: &str,
+        args: &mut I,
+    ) -> Result<bool, String> {
+        match flag {
+            "--marathon" => self.kind = ModeKind::Marathon,
+            "--ultra" => self.kind = ModeKind::Ultra,
+            "--time-limit" => self.time_limit = parse_value(flag, args.next())?,
+            "--line-cap" => self.line_cap = parse_value(flag, args.next())?,
+            "--endless" => self.endless = true,
+            "--dig" => self.kind = ModeKind::Dig,
+            "--lines" => self.target = parse_value(flag, args.next())?,
+            "--messiness" => self.messiness = Some(parse_value(flag, args.next())?),
+            "--hole-change" => self.hole_change = Some(parse_value(flag, args.next())?),
+            "--survival" => self.kind = ModeKind::Survival,
+            "--interval" => self.interval = parse_value(flag, args.next())?,
+            "--zen" => self.kind = ModeKind::Zen,
+            "--no-gravity" => self.gravity = false,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    pub fn mode(&self) -> Mode {
+        match self.kind {
+            ModeKind::Marathon => Mode::marathon(self.line_cap, self.endless),
+            ModeKind::Ultra => Mode::ultra(self.time_limit),
+            ModeKind::Dig => Mode::dig(self.target, self.holes(DIG_HOLES)),
+            ModeKind::Survival => Mode::survival(self.interval, self.holes(SURVIVAL_HOLES)),
+            ModeKind::Zen => Mode::zen(self.gravity),
+        }
+    }
+
+    /// `default` with the garbage settings given on the command line.
+    fn holes(&self, default: HoleRule) -> HoleRule {
+        HoleRule {
+            change: self.hole_change.unwrap_or(default.change),
+            messiness: self.messiness.unwrap_or(default.messiness),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Marathon { line_cap: u32, endless: bool },
+    Ultra { time_limit: Duration },
+    Dig { target: u32, holes: HoleRule },
+    Survival { interval: Duration, holes: HoleRule },
+    Zen { gravity: bool },
+}
+
+/// Runtime state of the modes that change the board over time or keep an undo history.
+pub struct ModeState {
+    next_garbage: Duration,
+    garbage_pushed: u32,
+    /// Snapshots taken whenever a piece spawns, the last one being the current piece.
+    history: VecDeque<Game>,
+}
+
+impl ModeState {
+    pub fn new(mode: &Mode) -> ModeState {
+        ModeState {
+            next_garbage: mode.garbage_interval(0),
+            garbage_pushed: 0,
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Mode {
+    pub fn marathon(line_cap: u32, endless: bool) -> Mode {
+        Mode::Marathon { line_cap, endless }
+    }
+
+    pub fn ultra(seconds: u64) -> Mode {
+        Mode::Ultra {
+            time_limit: Duration::from_secs(seconds),
+        }
+    }
+
+    pub fn dig(target: u32, holes: HoleRule) -> Mode {
+        Mode::Dig { target, holes }
+    }
+
+    pub fn survival(seconds: u64, holes: HoleRule) -> Mode {
+        Mode::Survival {
+            interval: Duration::from_secs(seconds),
+            holes,
+        }
+    }
+
+    pub fn zen(gravity: bool) -> Mode {
+        Mode::Zen { gravity }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Mode::Marathon { .. } => "MARATHON",
+            Mode::Ultra { .. } => "ULTRA",
+            Mode::Dig { .. } => "DIG",
+            Mode::Survival { .. } => "SURVIVAL",
+            Mode::Zen { .. } => "ZEN",
+        }
+    }
+
+    pub fn highscore_file(&self) -> Option<&'static str> {
+        match *self {
+            Mode::Marathon { .. } => Some(MARATHON_HIGHSCORE_FILE),
+            Mode::Ultra { .. } => Some(ULTRA_HIGHSCORE_FILE),
+            Mode::Dig { .. } | Mode::Survival { .. } | Mode::Zen { .. } => None,
+        }
+    }
+
+    pub fn has_gravity(&self) -> bool {
+        match *self {
+            Mode::Zen { gravity } => gravity,
+            _ => true,
+        }
+    }
+
+    /// Whether a piece spawning inside the stack ends the game. Otherwise the board is
+    /// cleared and play goes on.
+    pub fn can_top_out(&self) -> bool {
+        !matches!(*self, Mode::Zen { .. })
+    }
+
+    /// Prepares the starting board, which always has room for the first garbage rows.
+    pub fn setup(&self, game: &mut Game) {
+        if let Mode::Dig { .. } = *self {
+            self.before_spawn(game);
+        }
+    }
+
+    /// Called whenever a new piece is about to spawn, i.e. after every lock. Returns false if
+    /// the garbage refilling the board pushed the stack out of it.
+    pub fn before_spawn(&self, game: &mut Game) -> bool {
+        if let Mode::Dig { target, holes } = *self {
+            let on_board = game.garbage_rows();
+            let remaining =
+                (target as usize).saturating_sub(game.garbage_lines as usize + on_board);
+            let missing = DIG_VISIBLE_ROWS.saturating_sub(on_board);
+            return game.insert_garbage_rows(remaining.min(missing), holes);
+        }
+        true
+    }
+
+    /// Called once a new piece has spawned.
+    pub fn after_spawn(&self, game: &Game, state: &mut ModeState) {
+        if let Mode::Zen { .. } = *self {
+            state.history.push_back(game.clone());
+            // The snapshot of the current piece, and one per lock that can be undone.
+            if state.history.len() > ZEN_UNDO_LEVELS + 1 {
+                state.history.pop_front();
+            }
+        }
+    }
+
+    /// Restores the game as it was before the last piece lock, with that piece back at its
+    /// spawn position. Returns false if there is nothing to undo.
+    pub fn undo(&self, game: &mut Game, state: &mut ModeState) -> bool {
+        if let Mode::Zen { .. } = *self {
+            if state.history.len() >= 2 {
+                state.history.pop_back();
+                if let Some(snapshot) = state.history.back() {
+                    *game = snapshot.clone();
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Pushes the garbage rows that are due. Returns false if the stack overflowed.
+    pub fn update(&self, game: &mut Game, state: &mut ModeState, start: &SystemTime) -> bool {
+        self.update_elapsed(game, state, elapsed(start))
+    }
+
+    /// Like `update`, `elapsed` after the start of the game.
+    pub fn update_elapsed(
+        &self,
+        game: &mut Game,
+        state: &mut ModeState,
+        elapsed: Duration,
+    ) -> bool {
+        if let Mode::Survival { holes, .. } = *self {
+            while elapsed >= state.next_garbage {
+                if !game.insert_garbage_rows(1, holes) {
+                    return false;
+                }
+                state.garbage_pushed += 1;
+                state.next_garbage += self.garbage_interval(state.garbage_pushed);
+            }
+        }
+        true
+    }
+
+    /// Delay before the next garbage row once `pushed` rows have already been pushed.
+    fn garbage_interval(&self, pushed: u32) -> Duration {
+        match *self {
+            Mode::Survival { interval, .. } => interval
+                .checked_sub(SURVIVAL_INTERVAL_STEP * pushed)
+                .unwrap_or(SURVIVAL_MIN_INTERVAL)
+                .max(SURVIVAL_MIN_INTERVAL),
+            _ => Duration::new(0, 0),
+        }
+    }
+
+    /// Time shown on the HUD: remaining time for Ultra, elapsed time for Dig and Survival and
+    /// `None` for modes without a clock.
+    pub fn clock(&self, start: &SystemTime) -> Option<Duration> {
+        self.clock_elapsed(elapsed(start))
+    }
+
+    /// Like `clock`, `elapsed` after the start of the game.
+    pub fn clock_elapsed(&self, elapsed: Duration) -> Option<Duration> {
+        match *self {
+            Mode::Marathon { .. } | Mode::Zen { .. } => None,
+            Mode::Ultra { time_limit } => Some(
+                time_limit
+                    .checked_sub(elapsed)
+                    .unwrap_or_else(|| Duration::new(0, 0)),
+            ),
+            Mode::Dig { .. } | Mode::Survival { .. } => Some(elapsed),
+        }
+    }
+
+    pub fn is_over(&self, start: &SystemTime) -> bool {
+        self.is_over_elapsed(elapsed(start))
+    }
+
+    /// Like `is_over`, `elapsed` after the start of the game.
+    pub fn is_over_elapsed(&self, elapsed: Duration) -> bool {
+        match *self {
+            Mode::Ultra { .. } => self.clock_elapsed(elapsed) == Some(Duration::new(0, 0)),
+            _ => false,
+        }
+    }
+
+    /// Whether the goal of the mode has been reached, e.g. the marathon line cap.
+    pub fn is_complete(&self, game: &Game) -> bool {
+        match *self {
+            Mode::Marathon {
+                line_cap,
+                endless: false,
+            } => game.num_lines >= line_cap,
+            Mode::Dig { target, .. } => game.garbage_lines >= target,
+            _ => false,
+        }
+    }
+
+    /// Whether the player may keep playing after completing the mode.
+    pub fn can_continue(&self) -> bool {
+        matches!(*self, Mode::Marathon { .. })
+    }
+
+    /// The same mode with its goal removed, used to keep playing after completing it.
+    pub fn endless(&self) -> Mode {
+        match *self {
+            Mode::Marathon { line_cap, .. } => Mode::marathon(line_cap, true),
+            mode => mode,
+        }
+    }
+}
+
+fn elapsed(start: &SystemTime) -> Duration {
+    start.elapsed().unwrap_or_else(|_| Duration::new(0, 0))
+}
+
+pub fn format_time(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{}:{:02}.{}",
+        secs / 60,
+        secs % 60,
+        duration.subsec_millis() / 100
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Action;
+
+    #[test]
+    fn zen_undoes_up_to_its_undo_levels() {
+        let mode = Mode::zen(false);
+        let mut state = ModeState::new(&mode);
+        let mut game = Game::with_seed(1);
+        for _ in 0..ZEN_UNDO_LEVELS + 20 {
+            if game.current_piece.is_none() {
+                let piece = game.create_new_tetromino();
+                if !piece.test_current_position(&game.game_map) {
+                    game.clear_board();
+                }
+                game.current_piece = Some(piece);
+            }
+            mode.after_spawn(&game, &mut state);
+            game.apply_action(Action::HardDrop);
+        }
+        assert_eq!(state.history.len(), ZEN_UNDO_LEVELS + 1);
+        let undone = (0..)
+            .take_while(|_| mode.undo(&mut game, &mut state))
+            .count();
+        assert_eq!(undone, ZEN_UNDO_LEVELS);
+    }
+
+    #[test]
+    fn dig_refills_top_out_a_full_stack() {
+        let mode = Mode::dig(DIG_VISIBLE_ROWS as u32 * 2, DIG_HOLES);
+        let mut game = Game::with_seed(1);
+        assert!(mode.before_spawn(&mut game));
+        assert_eq!(game.garbage_rows(), DIG_VISIBLE_ROWS);
+
+        // The player cleared every garbage row with a stack reaching the top.
+        for row in game.game_map.iter_mut() {
+            *row = vec![0; row.len()];
+            row[0] = 1;
+        }
+        game.garbage_lines = DIG_VISIBLE_ROWS as u32;
+        assert!(!mode.before_spawn(&mut game));
+    }
+}
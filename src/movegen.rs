@@ -0,0 +1,319 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::finesse::AutoShift;
+use crate::game::{self, Action};
+use crate::tetromino::Tetromino;
+
+/// Where a piece ends up, and the shortest sequence of actions bringing it there from its
+/// current position, hard drop included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub state: u8,
+    pub x: isize,
+    pub y: usize,
+    pub actions: Vec<Action>,
+    /// Whether locking the piece there scores a T-spin.
+    pub t_spin: bool,
+}
+
+/// Moves tried from every position, a hard drop ending every sequence.
+const MOVES: [Action; 4] = [
+    Action::Left,
+    Action::Right,
+    Action::Rotate,
+    Action::SoftDrop,
+];
+
+/// Pieces stick out of their 4x4 box by at most this many columns on the left.
+const MARGIN: isize = 3;
+
+/// Every placement where `piece` can lock, found by a breadth-first search over the positions
+/// it can reach from where it is with the moves of the game, so tucks and spins are found
+/// along with the plain drops. Each placement comes with the shortest sequence of actions
+/// reaching it. A T piece can get the same placement twice, with and without a T-spin.
+pub fn placements(game_map: &[Vec<u8>], piece: &Tetromino) -> Vec<Placement> {
+    let mut placements = Vec::new();
+    if !piece.test_current_position(game_map) {
+        return placements;
+    }
+    let rows = game_map.len();
+    let width = game_map[0].len() as isize + MARGIN;
+    let states = piece.states.len();
+    // Only T pieces care whether they got where they are with a rotation.
+    let spins = if piece.is_t() { 2 } else { 1 };
+    let index = |state: usize, x: isize, y: usize, rotated: bool| {
+        (((y * states + state) * width as usize + (x + MARGIN) as usize) * spins)
+            + (rotated && spins == 2) as usize
+    };
+    let size = rows * states * width as usize * spins;
+
+    // Action leading to each position and the position it came from.
+    let mut parents: Vec<Option<(usize, Action)>> = vec![None; size];
+    let mut visited = vec![false; size];
+    let mut placed = vec![false; size];
+    let mut landings = Landings {
+        rows: vec![None; rows * states * width as usize],
+        width: width as usize,
+        states,
+    };
+    let mut queue = VecDeque::new();
+    let mut moving = piece.clone();
+
+    let start = index(piece.current_state as usize, piece.x, piece.y, false);
+    visited[start] = true;
+    queue.push_back((piece.current_state, piece.x, piece.y, false, start));
+    while let Some((state, x, y, rotated, node)) = queue.pop_front() {
+        let landing = drop(&mut landings, piece, game_map, state as usize, x, y);
+        // Dropping the piece is a move, which loses the rotation.
+        let t_spin = rotated && landing == y && {
+            moving.current_state = state;
+            moving.x = x;
+            moving.y = y;
+            game::t_spin_corners(game_map, &moving)
+        };
+        let target = index(state as usize, x, landing, t_spin);
+        if !placed[target] {
+            placed[target] = true;
+            let mut actions = path(&parents, node);
+            actions.push(Action::HardDrop);
+            placements.push(Placement {
+                state,
+                x,
+                y: landing,
+                actions,
+                t_spin,
+            });
+        }
+
+        for action in MOVES.iter() {
+            moving.current_state = state;
+            moving.x = x;
+            moving.y = y;
+            let moved = match *action {
+                Action::Left => moving.change_position(game_map, x - 1, y),
+                Action::Right => moving.change_position(game_map, x + 1, y),
+                Action::Rotate => moving.rotate(game_map),
+                _ => moving.change_position(game_map, x, y + 1),
+            };
+            if !moved {
+                continue;
+            }
+            let rotated = *action == Action::Rotate;
+            let next = index(moving.current_state as usize, moving.x, moving.y, rotated);
+            if !visited[next] {
+                visited[next] = true;
+                parents[next] = Some((node, *action));
+                queue.push_back((moving.current_state, moving.x, moving.y, rotated, next));
+            }
+        }
+    }
+    placements
+}
+
+/// Key presses the finesse search tries from every position: a tap of left, right or rotate,
+/// left or right held until it is let go or the piece stops, and soft drop held until it
+/// reaches the floor.
+const PRESSES: [Press; 6] = [
+    Press::Tap(Action::Left),
+    Press::Tap(Action::Right),
+    Press::Tap(Action::Rotate),
+    Press::Shift(-1),
+    Press::Shift(1),
+    Press::SoftDrop,
+];
+
+#[derive(Clone, Copy)]
+enum Press {
+    Tap(Action),
+    /// Auto shift in this direction.
+    Shift(isize),
+    SoftDrop,
+}
+
+/// Least key presses bringing `piece` from where it is to every place it can lock, the press
+/// locking it included, by the cases it covers there. Holding left or right is one press that
+/// takes the piece as far as it goes, or to any column on the way when the repeat rate of
+/// `shift` leaves time to let go of the key there.
+pub fn least_presses(
+    game_map: &[Vec<u8>],
+    piece: &Tetromino,
+    shift: AutoShift,
+) -> HashMap<Vec<(usize, usize)>, u32> {
+    let mut least = HashMap::new();
+    if !piece.test_current_position(game_map) {
+        return least;
+    }
+    let rows = game_map.len();
+    let width = game_map[0].len() as isize + MARGIN;
+    let states = piece.states.len();
+    let mut landings = Landings {
+        rows: vec![None; rows * states * width as usize],
+        width: width as usize,
+        states,
+    };
+    let mut visited = vec![false; rows * states * width as usize];
+    let mut queue = VecDeque::new();
+    let mut moving = piece.clone();
+
+    visited[landings.index(piece.current_state as usize, piece.x, piece.y)] = true;
+    queue.push_back((piece.current_state, piece.x, piece.y, 0));
+    while let Some((state, x, y, presses)) = queue.pop_front() {
+        let landing = drop(&mut landings, piece, game_map, state as usize, x, y);
+        least
+            .entry(cases(piece, state, x, landing))
+            .or_insert(presses + 1);
+
+        for press in PRESSES.iter() {
+            moving.current_state = state;
+            moving.x = x;
+            moving.y = y;
+            let moved = match *press {
+                Press::Tap(Action::Rotate) => moving.rotate(game_map),
+                Press::Tap(Action::Left) => moving.change_position(game_map, x - 1, y),
+                Press::Tap(_) => moving.change_position(game_map, x + 1, y),
+                Press::Shift(step) => {
+                    let mut moved = false;
+                    while moving.change_position(game_map, moving.x + step, y) {
+                        moved = true;
+                        if shift.stops_anywhere() {
+                            let next = landings.index(moving.current_state as usize, moving.x, y);
+                            visit(&mut visited, &mut queue, next, &moving, presses + 1);
+                        }
+                    }
+                    moved
+                }
+                Press::SoftDrop => landing != y && moving.change_position(game_map, x, landing),
+            };
+            if moved {
+                let next = landings.index(moving.current_state as usize, moving.x, moving.y);
+                visit(&mut visited, &mut queue, next, &moving, presses + 1);
+            }
+        }
+    }
+    least
+}
+
+/// Queues the position of `piece` at `index`, reached with `presses`, unless it was before.
+fn visit(
+    visited: &mut [bool],
+    queue: &mut VecDeque<(u8, isize, usize, u32)>,
+    index: usize,
+    piece: &Tetromino,
+    presses: u32,
+) {
+    if !visited[index] {
+        visited[index] = true;
+        queue.push_back((piece.current_state, piece.x, piece.y, presses));
+    }
+}
+
+/// Cases covered by `piece` in `state` at (`x`, `y`), as (row, column), sorted.
+pub fn cases(piece: &Tetromino, state: u8, x: isize, y: usize) -> Vec<(usize, usize)> {
+    let mut cases = Vec::new();
+    for (line_num, line) in piece.states[state as usize].iter().enumerate() {
+        for (case_num, case) in line.iter().enumerate() {
+            if *case != 0 {
+                cases.push((y + line_num, (x + case_num as isize) as usize));
+            }
+        }
+    }
+    cases.sort_unstable();
+    cases
+}
+
+/// Row each position of the piece lands on, filled in as the search goes.
+struct Landings {
+    rows: Vec<Option<usize>>,
+    width: usize,
+    states: usize,
+}
+
+impl Landings {
+    fn index(&self, state: usize, x: isize, y: usize) -> usize {
+        (y * self.states + state) * self.width + (x + MARGIN) as usize
+    }
+}
+
+/// Row where the piece at (`x`, `y`) in `state` lands, remembering it for every row on the way.
+fn drop(
+    landings: &mut Landings,
+    piece: &Tetromino,
+    game_map: &[Vec<u8>],
+    state: usize,
+    x: isize,
+    y: usize,
+) -> usize {
+    let mut landing = y;
+    let row = loop {
+        if let Some(row) = landings.rows[landings.index(state, x, landing)] {
+            break row;
+        }
+        if !piece.test_position(game_map, state, x, landing + 1) {
+            break landing;
+        }
+        landing += 1;
+    };
+    for above in y..=landing {
+        let index = landings.index(state, x, above);
+        landings.rows[index] = Some(row);
+    }
+    row
+}
+
+fn path(parents: &[Option<(usize, Action)>], mut node: usize) -> Vec<Action> {
+    let mut actions = Vec::new();
+    while let Some((parent, action)) = parents[node] {
+        actions.push(action);
+        node = parent;
+    }
+    actions.reverse();
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::game::Game;
+    use crate::tetromino;
+
+    /// The board of `rows` and a new piece of `kind` above it.
+    fn setup(rows: &str, kind: u8) -> (Vec<Vec<u8>>, Tetromino) {
+        let mut game = Game::with_seed(0);
+        board::load(&board::parse(rows).unwrap(), &mut game).unwrap();
+        (game.game_map, tetromino::from_kind(kind).unwrap())
+    }
+
+    /// Placements of `piece` covering exactly `cases`.
+    fn covering<'a>(
+        placements: &'a [Placement],
+        piece: &'a Tetromino,
+        cases: &'a [(usize, usize)],
+    ) -> impl Iterator<Item = &'a Placement> {
+        placements.iter().filter(move |placement| {
+            super::cases(piece, placement.state, placement.x, placement.y) == cases
+        })
+    }
+
+    #[test]
+    fn finds_tucks_under_overhangs() {
+        let (game_map, piece) = setup("GGGGGG....\n..........", 1);
+        let placements = placements(&game_map, &piece);
+        let tuck = [(15, 0), (15, 1), (15, 2), (15, 3)];
+        let placement = covering(&placements, &piece, &tuck).next().unwrap();
+        assert!(placement.actions.contains(&Action::SoftDrop));
+        assert_eq!(placement.actions.last(), Some(&Action::HardDrop));
+    }
+
+    /// The T turned flat once it is in the slot, as in the T-spin single puzzle.
+    #[test]
+    fn finds_t_spins() {
+        let (game_map, piece) = setup("..........\nGG..GGGGGG\nGG...GGGGG\nGGG.GGGGGG", 7);
+        let placements = placements(&game_map, &piece);
+        let slot = [(13, 3), (14, 2), (14, 3), (14, 4)];
+        let placement = covering(&placements, &piece, &slot)
+            .find(|placement| placement.t_spin)
+            .unwrap();
+        assert!(placement.actions.contains(&Action::Rotate));
+    }
+}
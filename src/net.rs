@@ -0,0 +1,333 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use crate::battle::{Battle, Outcome};
+use crate::game::Action;
+
+const HELLO: u8 = 0;
+const INPUTS: u8 = 1;
+const QUIT: u8 = 2;
+const JOIN: u8 = 3;
+const START: u8 = 4;
+
+/// Frames between reading an input and applying it, which hides the network latency.
+pub const INPUT_DELAY: u32 = 3;
+
+/// Match settings chosen by the host.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub seeds: [u64; 2],
+    pub garbage_delay: u32,
+    pub input_delay: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// Sent by the host once the client is connected.
+    Hello(Settings),
+    /// Actions of the sender for the given frame.
+    Inputs {
+        frame: u32,
+        actions: Vec<Action>,
+    },
+    Quit,
+    /// Sent to a server to join a room, creating it if nobody is waiting there.
+    Join {
+        room: String,
+    },
+    /// Sent by a server to both players of a room once it is full.
+    Start {
+        player: u8,
+        settings: Settings,
+    },
+}
+
+impl Message {
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        match *self {
+            Message::Hello(settings) => {
+                bytes.push(HELLO);
+                bytes.extend_from_slice(&settings.seeds[0].to_be_bytes());
+                bytes.extend_from_slice(&settings.seeds[1].to_be_bytes());
+                bytes.extend_from_slice(&settings.garbage_delay.to_be_bytes());
+                bytes.extend_from_slice(&settings.input_delay.to_be_bytes());
+            }
+            Message::Inputs { frame, ref actions } => {
+                bytes.push(INPUTS);
+                bytes.extend_from_slice(&frame.to_be_bytes());
+                bytes.push(actions.len() as u8);
+                bytes.extend(actions.iter().map(|action| action_to_byte(*action)));
+            }
+            Message::Quit => bytes.push(QUIT),
+            Message::Join { ref room } => {
+                bytes.push(JOIN);
+                bytes.push(room.len() as u8);
+                bytes.extend_from_slice(room.as_bytes());
+            }
+            Message::Start { player, settings } => {
+                bytes.push(START);
+                bytes.push(player);
+                bytes.extend_from_slice(&settings.seeds[0].to_be_bytes());
+                bytes.extend_from_slice(&settings.seeds[1].to_be_bytes());
+                bytes.extend_from_slice(&settings.garbage_delay.to_be_bytes());
+                bytes.extend_from_slice(&settings.input_delay.to_be_bytes());
+            }
+        }
+        writer.write_all(&bytes)?;
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Message> {
+        match read_u8(reader)? {
+            HELLO => Ok(Message::Hello(read_settings(reader)?)),
+            INPUTS => {
+                let frame = read_u32(reader)?;
+                let count = read_u8(reader)?;
+                let mut actions = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    actions.push(action_from_byte(read_u8(reader)?)?);
+                }
+                Ok(Message::Inputs { frame, actions })
+            }
+            QUIT => Ok(Message::Quit),
+            JOIN => {
+                let mut room = vec![0; read_u8(reader)? as usize];
+                reader.read_exact(&mut room)?;
+                match String::from_utf8(room) {
+                    Ok(room) => Ok(Message::Join { room }),
+                    Err(_) => Err(invalid_data("room name is not UTF-8".to_owned())),
+                }
+            }
+            START => Ok(Message::Start {
+                player: read_u8(reader)?,
+                settings: read_settings(reader)?,
+            }),
+            tag => Err(invalid_data(format!("unknown message {}", tag))),
+        }
+    }
+}
+
+fn action_to_byte(action: Action) -> u8 {
+    match action {
+        Action::Left => 0,
+        Action::Right => 1,
+        Action::SoftDrop => 2,
+        Action::Rotate => 3,
+        Action::HardDrop => 4,
+        Action::Hold => 5,
+    }
+}
+
+fn action_from_byte(byte: u8) -> io::Result<Action> {
+    match byte {
+        0 => Ok(Action::Left),
+        1 => Ok(Action::Right),
+        2 => Ok(Action::SoftDrop),
+        3 => Ok(Action::Rotate),
+        4 => Ok(Action::HardDrop),
+        5 => Ok(Action::Hold),
+        _ => Err(invalid_data(format!("unknown action {}", byte))),
+    }
+}
+
+fn read_settings<R: Read>(reader: &mut R) -> io::Result<Settings> {
+    Ok(Settings {
+        seeds: [read_u64(reader)?, read_u64(reader)?],
+        garbage_delay: read_u32(reader)?,
+        input_delay: read_u32(reader)?,
+    })
+}
+
+pub(crate) fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+pub fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Connection> {
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Waits for a single client on `addr` and sends it the match settings.
+    pub fn host(addr: &str, settings: Settings) -> io::Result<Connection> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+        let mut connection = Connection::new(stream)?;
+        connection.send(&Message::Hello(settings))?;
+        Ok(connection)
+    }
+
+    /// Connects to a host on `addr` and waits for the match settings.
+    pub fn connect(addr: &str) -> io::Result<(Connection, Settings)> {
+        let mut connection = Connection::new(TcpStream::connect(addr)?)?;
+        match connection.receive()? {
+            Message::Hello(settings) => Ok((connection, settings)),
+            message => Err(invalid_data(format!("expected hello, got {:?}", message))),
+        }
+    }
+
+    /// Joins `room` on the server at `addr` and waits for an opponent. Returns the index of
+    /// the local player along with the match settings; the server then relays everything
+    /// sent on the connection to the opponent.
+    pub fn join(addr: &str, room: &str) -> io::Result<(Connection, usize, Settings)> {
+        if room.len() > u8::MAX as usize {
+            return Err(invalid_data(format!("room name too long: {}", room)));
+        }
+        let mut connection = Connection::new(TcpStream::connect(addr)?)?;
+        connection.send(&Message::Join {
+            room: room.to_owned(),
+        })?;
+        match connection.receive()? {
+            Message::Start { player, settings } if player < 2 => {
+                Ok((connection, player as usize, settings))
+            }
+            message => Err(invalid_data(format!("expected start, got {:?}", message))),
+        }
+    }
+
+    /// Whether the other end is still connected, found without waiting for it to send
+    /// anything.
+    pub fn is_open(&self) -> bool {
+        if !self.reader.buffer().is_empty() {
+            return true;
+        }
+        let stream = self.reader.get_ref();
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let open = match stream.peek(&mut [0]) {
+            Ok(read) => read > 0,
+            Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+        };
+        stream.set_nonblocking(false).is_ok() && open
+    }
+
+    pub fn into_parts(self) -> (BufReader<TcpStream>, TcpStream) {
+        (self.reader, self.writer)
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        message.write_to(&mut self.writer)
+    }
+
+    pub fn receive(&mut self) -> io::Result<Message> {
+        Message::read_from(&mut self.reader)
+    }
+}
+
+/// A network match as seen by the frontend.
+pub trait Session {
+    /// Sends the local actions of this frame and moves the battle forward. Returns false if
+    /// the opponent left.
+    fn advance(&mut self, actions: Vec<Action>) -> io::Result<bool>;
+    /// The battle to display.
+    fn battle(&self) -> &Battle;
+    /// The final result, once both players agree on it.
+    fn outcome(&self) -> Option<Outcome>;
+    /// Index of the local player in the battle: 0 for the host, 1 for the client.
+    fn local(&self) -> usize;
+    fn quit(&mut self);
+}
+
+/// A battle against a remote player. Both sides simulate both boards and only exchange their
+/// actions, the battle waiting for the remote actions of a frame before simulating it.
+pub struct Lockstep {
+    battle: Battle,
+    local: usize,
+    connection: Connection,
+    input_delay: u32,
+    local_inputs: VecDeque<Vec<Action>>,
+}
+
+impl Lockstep {
+    pub fn new(connection: Connection, local: usize, settings: Settings) -> Lockstep {
+        Lockstep {
+            battle: Battle::new(settings.seeds, settings.garbage_delay),
+            local,
+            connection,
+            input_delay: settings.input_delay,
+            local_inputs: (0..settings.input_delay).map(|_| Vec::new()).collect(),
+        }
+    }
+}
+
+impl Session for Lockstep {
+    fn advance(&mut self, actions: Vec<Action>) -> io::Result<bool> {
+        let frame = self.battle.frame;
+        self.connection.send(&Message::Inputs {
+            frame: frame + self.input_delay,
+            actions: actions.clone(),
+        })?;
+        self.local_inputs.push_back(actions);
+        let local_actions = self.local_inputs.pop_front().unwrap_or_default();
+
+        let remote_actions = if frame < self.input_delay {
+            Vec::new()
+        } else {
+            match self.connection.receive()? {
+                Message::Inputs {
+                    frame: remote_frame,
+                    actions,
+                } if remote_frame == frame => actions,
+                Message::Quit => return Ok(false),
+                message => {
+                    return Err(invalid_data(format!(
+                        "expected inputs for frame {}, got {:?}",
+                        frame, message
+                    )))
+                }
+            }
+        };
+
+        if self.local == 0 {
+            self.battle.step([&local_actions, &remote_actions]);
+        } else {
+            self.battle.step([&remote_actions, &local_actions]);
+        }
+        Ok(true)
+    }
+
+    fn battle(&self) -> &Battle {
+        &self.battle
+    }
+
+    fn outcome(&self) -> Option<Outcome> {
+        self.battle.outcome()
+    }
+
+    fn local(&self) -> usize {
+        self.local
+    }
+
+    fn quit(&mut self) {
+        let _ = self.connection.send(&Message::Quit);
+    }
+}
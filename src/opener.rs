@@ -0,0 +1,374 @@
+//! Openers: setups built with the first pieces of a game, such as a perfect clear opener. They
+//! are read from files like this one, the board being a text board (see `board`) or the last
+//! page of a fumen once its piece locked:
+//!
+//! ```text
+//! name: T-spin single opener
+//! board:
+//! .....OO..I
+//! JJ...OOLLI
+//! JZZ...SSLI
+//! J.ZZ.SS.LI
+//! ```
+//!
+//! Every piece of the opener is written with its letter, so the trainer knows where each piece
+//! it deals goes. Openers of several bags use the letters of their pieces several times, and
+//! openers built differently depending on the order of the pieces have a board per variant.
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::path::Path;
+
+use crate::board;
+use crate::files;
+use crate::fumen;
+use crate::game::{Action, Game, LineClear, PREVIEW_SIZE};
+use crate::tetromino;
+
+/// Extension of opener files.
+pub const EXTENSION: &str = "opener";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Opener {
+    /// Name of the file without its extension.
+    pub id: String,
+    pub name: String,
+    /// Variants of the opener for different orders of the pieces, each the rows at the bottom
+    /// of the board once built, from top to bottom.
+    pub boards: Vec<Vec<Vec<u8>>>,
+}
+
+impl Opener {
+    /// Reads the opener `id` written in `text`. Fails on boards with garbage, full rows or
+    /// letters that don't make whole pieces, and on variants of different sizes.
+    pub fn parse(id: &str, text: &str) -> Result<Opener, String> {
+        let mut name = None;
+        let mut boards = Vec::new();
+        let mut lines = text.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = files::key_value(line)?;
+            match key {
+                "name" => name = Some(value.to_owned()),
+                "fumen" => {
+                    let pages = fumen::decode(value)?;
+                    let last = pages.last().ok_or("The fumen has no page")?;
+                    boards.push(fumen::trim(&last.locked_field()));
+                }
+                // The board goes on until the next key.
+                "board" => {
+                    let mut rows = Vec::new();
+                    while let Some(row) = lines.peek() {
+                        if row.contains(':') {
+                            break;
+                        }
+                        rows.push(*row);
+                        lines.next();
+                    }
+                    boards.push(board::parse(&rows.join("\n"))?);
+                }
+                _ => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+
+        if boards.is_empty() {
+            return Err("The opener has no board".to_owned());
+        }
+        for rows in &boards {
+            check(rows)?;
+        }
+        let opener = Opener {
+            id: id.to_owned(),
+            name: name.unwrap_or_else(|| id.to_owned()),
+            boards,
+        };
+        if opener
+            .boards
+            .iter()
+            .any(|rows| pieces(rows) != opener.pieces())
+        {
+            return Err("The variants of the opener have different numbers of pieces".to_owned());
+        }
+        Ok(opener)
+    }
+
+    /// Number of pieces the opener is built with.
+    pub fn pieces(&self) -> usize {
+        pieces(&self.boards[0])
+    }
+}
+
+fn pieces(rows: &[Vec<u8>]) -> usize {
+    rows.iter().flatten().filter(|case| **case != 0).count() / 4
+}
+
+/// Fails if `rows` can't be an opener.
+fn check(rows: &[Vec<u8>]) -> Result<(), String> {
+    for kind in 1..=8 {
+        let cases = rows.iter().flatten().filter(|case| **case == kind).count();
+        if kind == 8 && cases > 0 {
+            return Err("Openers are built on an empty board".to_owned());
+        }
+        if cases % 4 != 0 {
+            return Err(format!(
+                "The cases of {} don't make whole pieces",
+                board::letter(kind)
+            ));
+        }
+    }
+    if rows.iter().any(|row| !row.contains(&0)) {
+        return Err("Openers must not clear lines".to_owned());
+    }
+    if pieces(rows) == 0 {
+        return Err("The opener has no piece".to_owned());
+    }
+    board::load(rows, &mut Game::with_seed(0))
+}
+
+/// The openers of the files of `dir`, sorted by file name.
+pub fn load_dir(dir: &Path) -> Result<Vec<Opener>, String> {
+    files::load_files(dir, EXTENSION, Opener::parse)
+}
+
+/// How a training ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Built,
+    /// A piece of this kind locked somewhere else than the opener has one.
+    Deviation(u8),
+}
+
+/// A try at building an opener, with pieces dealt from shuffled bags of the seven pieces.
+#[derive(Clone)]
+pub struct Training {
+    pub game: Game,
+    /// Variants of the opener on boards as high as the one of the game, left out once a piece
+    /// strays from them. The last ones left stay when every variant is strayed from.
+    pub targets: Vec<Vec<Vec<u8>>>,
+    /// Pieces of the opener locked so far.
+    pub placed: usize,
+    /// Set once the opener is built or a piece strayed from it.
+    pub outcome: Option<Outcome>,
+    rng: StdRng,
+    /// Pieces left in the current bag, the next one last.
+    bag: Vec<u8>,
+}
+
+impl Training {
+    /// Starts building `opener`, its first piece spawned. The same `seed` deals the same bags.
+    pub fn new(opener: &Opener, seed: u64) -> Training {
+        let mut game = Game::with_seed(seed);
+        let targets = opener
+            .boards
+            .iter()
+            .map(|rows| {
+                board::load(rows, &mut game).expect("Opener boards fit on the board");
+                game.game_map.clone()
+            })
+            .collect();
+        game.clear_board();
+        let mut training = Training {
+            game,
+            targets,
+            placed: 0,
+            outcome: None,
+            rng: StdRng::seed_from_u64(seed),
+            bag: Vec::new(),
+        };
+        training.game.queue.clear();
+        for _ in 0..PREVIEW_SIZE {
+            training.deal();
+        }
+        training.spawn();
+        training
+    }
+
+    /// Plays `actions`, then spawns the next piece if the current one got locked. The training
+    /// is over as soon as a piece strays from the opener, or once every piece of it is placed.
+    pub fn apply_actions(&mut self, actions: &[Action]) -> Vec<LineClear> {
+        let mut clears = Vec::new();
+        for action in actions {
+            if self.outcome.is_some() {
+                break;
+            }
+            let held = self.game.hold.is_some();
+            let kind = self.game.current_piece.as_ref().map(|piece| piece.kind());
+            if let Some(clear) = self.game.apply_action(*action) {
+                let kind = kind.expect("Only a current piece locks");
+                let game_map = &self.game.game_map;
+                let matching: Vec<_> = self
+                    .targets
+                    .iter()
+                    .filter(|target| on_target(game_map, target))
+                    .cloned()
+                    .collect();
+                if clear.lines > 0 || matching.is_empty() {
+                    self.outcome = Some(Outcome::Deviation(kind));
+                } else {
+                    self.placed += 1;
+                    if matching.iter().any(|target| target == game_map) {
+                        self.outcome = Some(Outcome::Built);
+                    }
+                    self.targets = matching;
+                }
+                clears.push(clear);
+            }
+            // Holding with an empty hold took the next piece from the queue.
+            if !held && self.game.hold.is_some() {
+                self.replace_last();
+            }
+        }
+        self.spawn();
+        clears
+    }
+
+    /// Cases of the opener where the current piece can go: those of its kind that are still
+    /// empty, in any variant left. None when there is no room left for it, so it had better
+    /// be held.
+    pub fn overlay(&self) -> Option<Vec<Vec<u8>>> {
+        let kind = self.game.current_piece.as_ref()?.kind();
+        let mut overlay = self.game.game_map.clone();
+        let mut any = false;
+        for (row_num, row) in overlay.iter_mut().enumerate() {
+            for (column, case) in row.iter_mut().enumerate() {
+                let free = *case == 0
+                    && self
+                        .targets
+                        .iter()
+                        .any(|target| target[row_num][column] == kind);
+                *case = if free { kind } else { 0 };
+                any |= free;
+            }
+        }
+        if any {
+            Some(overlay)
+        } else {
+            None
+        }
+    }
+
+    /// Spawns the next piece if there is no current one, failing if it doesn't fit.
+    fn spawn(&mut self) {
+        if self.outcome.is_some() || self.game.current_piece.is_some() {
+            return;
+        }
+        let piece = self.game.create_new_tetromino();
+        self.replace_last();
+        if !piece.test_current_position(&self.game.game_map) {
+            self.outcome = Some(Outcome::Deviation(piece.kind()));
+        }
+        self.game.current_piece = Some(piece);
+    }
+
+    /// Swaps the random piece the game put at the back of the queue for one from the bag.
+    fn replace_last(&mut self) {
+        self.game.queue.pop_back();
+        self.deal();
+    }
+
+    /// Puts the next piece of the bag at the back of the queue, opening a new bag when the
+    /// current one is empty.
+    fn deal(&mut self) {
+        if self.bag.is_empty() {
+            self.bag = (1..=7).collect();
+            self.bag.shuffle(&mut self.rng);
+        }
+        let kind = self.bag.pop().expect("Bags are refilled when empty");
+        self.game
+            .queue
+            .push_back(tetromino::from_kind(kind).expect("Bags hold piece kinds"));
+    }
+}
+
+/// Whether every case of `game_map` is empty or holds the piece `target` has there.
+fn on_target(game_map: &[Vec<u8>], target: &[Vec<u8>]) -> bool {
+    game_map
+        .iter()
+        .flatten()
+        .zip(target.iter().flatten())
+        .all(|(case, target)| *case == 0 || case == target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen;
+    use std::collections::HashSet;
+
+    /// Whether the pieces of `target` can be locked in some order on `game_map`, each coming
+    /// from where pieces spawn, until the board is `target`. `tried` holds the boards known not
+    /// to lead there.
+    fn buildable(
+        game_map: &mut Vec<Vec<u8>>,
+        target: &[Vec<u8>],
+        tried: &mut HashSet<Vec<Vec<u8>>>,
+    ) -> bool {
+        if game_map.as_slice() == target {
+            return true;
+        }
+        if !tried.insert(game_map.clone()) {
+            return false;
+        }
+        for kind in 1..=7 {
+            let piece = tetromino::from_kind(kind).expect("Kinds from 1 to 7 are pieces");
+            for placement in movegen::placements(game_map, &piece) {
+                let cases = movegen::cases(&piece, placement.state, placement.x, placement.y);
+                if cases
+                    .iter()
+                    .any(|&(row, column)| game_map[row][column] != 0 || target[row][column] != kind)
+                {
+                    continue;
+                }
+                for &(row, column) in &cases {
+                    game_map[row][column] = kind;
+                }
+                if buildable(game_map, target, tried) {
+                    return true;
+                }
+                for &(row, column) in &cases {
+                    game_map[row][column] = 0;
+                }
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn the_openers_shipped_can_be_built() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("openers");
+        let openers = load_dir(&dir).unwrap();
+        assert!(openers.len() >= 4);
+        for opener in openers {
+            let training = Training::new(&opener, 0);
+            for target in &training.targets {
+                let mut game_map = target.iter().map(|row| vec![0; row.len()]).collect();
+                assert!(
+                    buildable(&mut game_map, target, &mut HashSet::new()),
+                    "{} can't be built",
+                    opener.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_openers() {
+        let error = |text| Opener::parse("bad", text).unwrap_err();
+        assert_eq!(error("name: Nothing"), "The opener has no board");
+        assert_eq!(
+            error("board:\nGGGG....II\nGGGG....II"),
+            "Openers are built on an empty board"
+        );
+        assert_eq!(
+            error("board:\nLL........"),
+            "The cases of L don't make whole pieces"
+        );
+        assert_eq!(
+            error("board:\n......JJ..\nIIIIIIIIJJ"),
+            "Openers must not clear lines"
+        );
+    }
+}
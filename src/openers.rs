@@ -0,0 +1,111 @@
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::events::{self, KeyMap};
+use crate::render::Renderer;
+use tetrs::board;
+use tetrs::opener::{self, Opener, Outcome, Training};
+
+/// Lists the openers of `dir` and trains the player on the one picked, until the player quits.
+pub fn run_openers(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, dir: &str) {
+    let openers = match opener::load_dir(Path::new(dir)) {
+        Ok(openers) => openers,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if openers.is_empty() {
+        eprintln!("No .{} file in {}", opener::EXTENSION, dir);
+        return;
+    }
+    let items: Vec<String> = openers
+        .iter()
+        .map(|opener| opener.name.to_uppercase())
+        .collect();
+    let mut selected = 0;
+
+    while crate::display_menu(renderer, event_pump, "OPENERS", &items, &mut selected) {
+        let opener = &openers[selected];
+        let mut seed = rand::random();
+        // Retrying deals the same bags again, building it again deals new ones.
+        while let Some(outcome) = train(renderer, event_pump, opener, seed) {
+            let mut lines = match outcome {
+                Outcome::Built => {
+                    seed = rand::random();
+                    vec!["OPENER BUILT".to_owned(), "ENTER: AGAIN".to_owned()]
+                }
+                Outcome::Deviation(kind) => vec![
+                    format!("{} PIECE OFF THE OPENER", board::letter(kind)),
+                    "ENTER: RETRY".to_owned(),
+                ],
+            };
+            lines.push("ESCAPE: MENU".to_owned());
+            if !crate::display_end_screen(renderer, event_pump, &lines) {
+                break;
+            }
+        }
+        renderer.overlay = None;
+        renderer.hint = None;
+    }
+}
+
+/// Builds `opener` with the bags of `seed` until it is built, a piece strays from it or the
+/// player quits (None). Backspace starts over with the same bags.
+fn train(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    opener: &Opener,
+    seed: u64,
+) -> Option<Outcome> {
+    let mut training = Training::new(opener, seed);
+    renderer.hint = None;
+    loop {
+        let mut quit = false;
+        let mut restart = false;
+        let mut export = false;
+        let actions = events::poll_actions(
+            event_pump,
+            &[KeyMap::single_player()],
+            &mut quit,
+            &mut restart,
+            &mut export,
+        );
+        if quit {
+            return None;
+        }
+        if restart {
+            training = Training::new(opener, seed);
+        }
+        training.apply_actions(&actions[0]);
+        if export {
+            crate::print_fumens(&[&training.game]);
+        }
+
+        renderer.overlay = training.overlay();
+        if let Some(Outcome::Deviation(_)) = training.outcome {
+            // Outline the opener over the stray piece.
+            renderer.overlay = None;
+            renderer.hint = Some(training.targets[0].clone());
+        }
+        renderer.draw_boards(&[(&training.game, &info(opener, &training))]);
+        if training.outcome.is_some() {
+            sleep(Duration::from_millis(1000));
+            return training.outcome;
+        }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+fn info(opener: &Opener, training: &Training) -> Vec<String> {
+    let mut texts = vec![
+        opener.name.to_uppercase(),
+        format!("PIECES: {}/{}", training.placed, opener.pieces()),
+    ];
+    if training.outcome.is_none() && training.overlay().is_none() {
+        texts.push("NOT IN THE OPENER: HOLD".to_owned());
+    }
+    texts.push("BACKSPACE: RETRY".to_owned());
+    texts
+}
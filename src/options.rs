@@ -0,0 +1,210 @@
+use std::fs;
+
+use crate::attack;
+use crate::board;
+use crate::bot;
+use crate::finesse::AutoShift;
+use crate::fumen::{self, Page};
+use crate::mode::{Mode, ModeArgs};
+use crate::net;
+use tetrs::parse_value;
+
+pub struct Options {
+    pub mode: Mode,
+    pub seed: Option<u64>,
+    pub versus: bool,
+    pub same_pieces: bool,
+    pub garbage_delay: u32,
+    pub host: Option<String>,
+    pub connect: Option<String>,
+    pub server: Option<String>,
+    pub room: String,
+    pub input_delay: u32,
+    pub rollback: bool,
+    /// Whether the bot plays instead of the player, or instead of player 2 in local versus.
+    pub bot: bool,
+    pub bot_delay: u32,
+    /// Beam width of the bot, which plays greedily without one.
+    pub beam: Option<usize>,
+    /// Milliseconds the beam search may take per piece, if it should depend on the time.
+    pub bot_time: Option<u64>,
+    /// Command running an external bot that plays instead of the player.
+    pub tbp: Option<String>,
+    /// Whether to act as a bot engine on the standard input and output instead of playing.
+    pub tbp_bot: bool,
+    /// Number of pieces of the headless game checking the external bot, if it should be run.
+    pub tbp_test: Option<u32>,
+    /// Whether to show where the pieces of a perfect clear go when there is one.
+    pub pc_hint: bool,
+    /// Board and piece the single player game starts from, the first page of `--fumen`.
+    pub fumen: Option<Page>,
+    /// Rows at the bottom of the board the single player game starts from, read from the
+    /// text file of `--board`.
+    pub board: Option<Vec<Vec<u8>>>,
+    /// Whether to edit the starting board before playing it.
+    pub editor: bool,
+    /// Directory of the puzzle files to play.
+    pub puzzles: Option<String>,
+    /// Directory of the opener files to train on.
+    pub openers: Option<String>,
+    /// Whether to train finesse instead of playing.
+    pub finesse: bool,
+    /// Delayed auto shift of the finesse trainer.
+    pub auto_shift: AutoShift,
+    /// Address spectators can watch the game from.
+    pub broadcast: Option<String>,
+    pub watch: Option<String>,
+    /// Latency in milliseconds of the headless rollback test, if it should be run.
+    pub latency_test: Option<u64>,
+    pub jitter: u64,
+}
+
+/// Room joined on a match server when none is given.
+const DEFAULT_ROOM: &str = "lobby";
+
+impl Options {
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut mode = ModeArgs::default();
+        let mut seed = None;
+        let mut versus = false;
+        let mut same_pieces = false;
+        let mut garbage_delay = attack::GARBAGE_DELAY;
+        let mut host = None;
+        let mut connect = None;
+        let mut server = None;
+        let mut room = DEFAULT_ROOM.to_owned();
+        let mut input_delay = net::INPUT_DELAY;
+        let mut rollback = false;
+        let mut bot = false;
+        let mut bot_delay = bot::BOT_DELAY;
+        let mut beam = None;
+        let mut bot_time = None;
+        let mut tbp = None;
+        let mut tbp_bot = false;
+        let mut tbp_test = None;
+        let mut pc_hint = false;
+        let mut fumen = None;
+        let mut board = None;
+        let mut editor = false;
+        let mut puzzles = None;
+        let mut openers = None;
+        let mut finesse = false;
+        let mut auto_shift = AutoShift::default();
+        let mut broadcast = None;
+        let mut watch = None;
+        let mut latency_test = None;
+        let mut jitter = 0;
+
+        // Skip the program name.
+        args.next();
+        while let Some(arg) = args.next() {
+            if mode.parse(&arg, &mut args)? {
+                continue;
+            }
+            match arg.as_str() {
+                "--versus" => versus = true,
+                "--same-pieces" => same_pieces = true,
+                "--garbage-delay" => garbage_delay = parse_value(&arg, args.next())?,
+                "--host" => host = Some(parse_value(&arg, args.next())?),
+                "--connect" => connect = Some(parse_value(&arg, args.next())?),
+                "--server" => server = Some(parse_value(&arg, args.next())?),
+                "--room" => room = parse_value(&arg, args.next())?,
+                "--input-delay" => input_delay = parse_value(&arg, args.next())?,
+                "--rollback" => rollback = true,
+                "--bot" => bot = true,
+                "--bot-delay" => bot_delay = parse_value(&arg, args.next())?,
+                "--beam" => beam = Some(parse_value(&arg, args.next())?),
+                "--bot-time" => bot_time = Some(parse_value(&arg, args.next())?),
+                "--tbp" => tbp = Some(parse_value(&arg, args.next())?),
+                "--tbp-bot" => tbp_bot = true,
+                "--tbp-test" => tbp_test = Some(parse_value(&arg, args.next())?),
+                "--pc-hint" => pc_hint = true,
+                "--fumen" => {
+                    let data: String = parse_value(&arg, args.next())?;
+                    let pages = fumen::decode(&data).map_err(|e| format!("{}: {}", arg, e))?;
+                    fumen = pages.into_iter().next();
+                }
+                "--editor" => editor = true,
+                "--board" => {
+                    let path: String = parse_value(&arg, args.next())?;
+                    let text = fs::read_to_string(&path)
+                        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+                    board = Some(board::parse(&text).map_err(|e| format!("{}: {}", path, e))?);
+                }
+                "--puzzles" => puzzles = Some(parse_value(&arg, args.next())?),
+                "--openers" => openers = Some(parse_value(&arg, args.next())?),
+                "--finesse" => finesse = true,
+                "--das" => auto_shift.das = parse_value(&arg, args.next())?,
+                "--arr" => auto_shift.arr = parse_value(&arg, args.next())?,
+                "--broadcast" => broadcast = Some(parse_value(&arg, args.next())?),
+                "--watch" => watch = Some(parse_value(&arg, args.next())?),
+                "--latency-test" => latency_test = Some(parse_value(&arg, args.next())?),
+                "--jitter" => jitter = parse_value(&arg, args.next())?,
+                "--seed" => seed = Some(parse_value(&arg, args.next())?),
+                _ => return Err(format!("Unknown argument: {}", arg)),
+            }
+        }
+        if fumen.is_some() && board.is_some() {
+            return Err("--fumen and --board both set the starting board".to_owned());
+        }
+        Ok(Options {
+            mode: mode.mode(),
+            seed,
+            versus,
+            same_pieces,
+            garbage_delay,
+            host,
+            connect,
+            server,
+            room,
+            input_delay,
+            rollback,
+            bot,
+            bot_delay,
+            beam,
+            bot_time,
+            tbp,
+            tbp_bot,
+            tbp_test,
+            pc_hint,
+            fumen,
+            board,
+            editor,
+            puzzles,
+            openers,
+            finesse,
+            auto_shift,
+            broadcast,
+            watch,
+            latency_test,
+            jitter,
+        })
+    }
+}
+
+pub fn usage() -> &'static str {
+    "Usage: tetrs [MODE] [--seed SEED] [--fumen DATA | --board FILE] [--pc-hint] [--broadcast ADDRESS]
+       tetrs --editor [MODE] [--seed SEED] [--fumen DATA | --board FILE]
+       tetrs --puzzles DIR
+       tetrs --openers DIR
+       tetrs --finesse [--das FRAMES] [--arr FRAMES] [--seed SEED]
+       tetrs --versus [--same-pieces] [--garbage-delay FRAMES] [--seed SEED]
+       tetrs --host ADDRESS [--rollback] [--same-pieces] [--garbage-delay FRAMES] [--input-delay FRAMES] [--seed SEED]
+       tetrs --connect ADDRESS [--rollback]
+       tetrs --server ADDRESS [--room NAME] [--rollback]
+       tetrs --watch ADDRESS
+       tetrs --latency-test MILLISECONDS [--jitter MILLISECONDS] [--input-delay FRAMES] [--seed SEED]
+       tetrs --tbp-bot [--beam WIDTH [--bot-time MILLISECONDS]]
+       tetrs --tbp-test PIECES [--tbp COMMAND] [--seed SEED]
+Every game accepts --broadcast ADDRESS to let others watch it with --watch, and
+--bot [--bot-delay FRAMES] [--beam WIDTH [--bot-time MILLISECONDS]] to let the computer
+play (player 2 in local versus), or --tbp COMMAND to let an external bot play.
+--fumen DATA starts from the board of a fumen, and F prints the fumen of the boards.
+--board FILE starts from a board written with a line per row of ., IJLOSZT and G.
+Modes:
+    --marathon [--line-cap LINES] [--endless]
+    --ultra [--time-limit SECONDS]
+    --dig [--lines LINES] [--hole-change PROBABILITY] [--messiness AMOUNT]
+    --survival [--interval SECONDS] [--hole-change PROBABILITY] [--messiness AMOUNT]
+    --zen [--no-gravity]"
+}
@@ -0,0 +1,365 @@
+//! Perfect clear finder: searches placements of the current piece, the hold and the queue that
+//! leave the board empty.
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use crate::bot;
+use crate::game::{Action, Game};
+use crate::movegen::{self, Placement};
+use crate::tetromino::{self, Tetromino};
+
+/// Lines perfect clears are usually searched within.
+pub const PC_LINES: usize = 4;
+/// Time the in-game hint may take to search a board, in milliseconds.
+pub const TIME_BUDGET: u64 = 50;
+
+/// Placements leading to a perfect clear.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    /// Placements to play in order, the actions of each starting with a hold when needed.
+    pub placements: Vec<Placement>,
+    /// Cases of the pieces of the solution, as if no line got cleared on the way: a board as
+    /// high as the one searched, empty where there is no piece.
+    pub board: Vec<Vec<u8>>,
+}
+
+/// State of a search, shared by every node.
+struct Finder<'a> {
+    /// Current piece then the queue, as piece kinds.
+    pieces: Vec<u8>,
+    current: &'a Tetromino,
+    deadline: Instant,
+    timed_out: bool,
+    /// Region, next piece and hold of the nodes known not to lead anywhere.
+    failed: HashSet<(Vec<u16>, usize, Option<u8>, bool)>,
+    placements: Vec<Placement>,
+    /// Cases of the placements so far, with the row they take on the searched board.
+    cases: Vec<(usize, usize, u8)>,
+}
+
+/// A board during the search.
+struct Node {
+    board: Vec<Vec<u8>>,
+    /// Row of the searched board every row comes from, empty rows added on top having none.
+    origins: Vec<Option<usize>>,
+    /// Rows at the bottom of the board every piece must fit in.
+    lines: usize,
+    next: usize,
+    hold: Option<u8>,
+    can_hold: bool,
+}
+
+/// The first perfect clear found within `max_lines` lines for the board, current piece, hold
+/// and queue of `game`, trying fewer lines first. Gives up after `time_budget`.
+pub fn find(game: &Game, max_lines: usize, time_budget: Duration) -> Option<Solution> {
+    let current = game.current_piece.as_ref()?;
+    let mut pieces = vec![current.kind()];
+    pieces.extend(game.queue.iter().map(Tetromino::kind));
+    let available = pieces.len() + game.hold.is_some() as usize;
+    let rows = game.game_map.len();
+    let columns = game.game_map[0].len();
+    let height = game
+        .game_map
+        .iter()
+        .position(|row| row.iter().any(|case| *case != 0))
+        .map_or(0, |top| rows - top);
+    let filled: usize = game
+        .game_map
+        .iter()
+        .map(|row| row.iter().filter(|case| **case != 0).count())
+        .sum();
+
+    let mut finder = Finder {
+        pieces,
+        current,
+        deadline: Instant::now() + time_budget,
+        timed_out: false,
+        failed: HashSet::new(),
+        placements: Vec::new(),
+        cases: Vec::new(),
+    };
+    for lines in height.max(1)..=max_lines.min(rows) {
+        let empty = lines * columns - filled;
+        if empty / 4 > available || !can_be_filled(&game.game_map[rows - lines..]) {
+            continue;
+        }
+        let node = Node {
+            board: game.game_map.clone(),
+            origins: (0..rows).map(Some).collect(),
+            lines,
+            next: 0,
+            hold: game.hold.as_ref().map(Tetromino::kind),
+            can_hold: game.can_hold,
+        };
+        finder.failed.clear();
+        if finder.search(&node) {
+            let mut board = vec![vec![0; columns]; rows];
+            for (y, x, case) in finder.cases {
+                board[y][x] = case;
+            }
+            return Some(Solution {
+                placements: finder.placements,
+                board,
+            });
+        }
+        if finder.timed_out {
+            return None;
+        }
+    }
+    None
+}
+
+impl<'a> Finder<'a> {
+    /// Whether the pieces left can clear the board of `node`, in which case the placements
+    /// and cases of the solution are kept.
+    fn search(&mut self, node: &Node) -> bool {
+        if node.lines == 0 {
+            return true;
+        }
+        if Instant::now() > self.deadline {
+            self.timed_out = true;
+            return false;
+        }
+        let rows = node.board.len();
+        let region: Vec<u16> = node.board[rows - node.lines..]
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, case)| **case != 0)
+                    .fold(0, |mask, (x, _)| mask | 1 << x)
+            })
+            .collect();
+        let key = (region, node.next, node.hold, node.can_hold);
+        if self.failed.contains(&key) {
+            return false;
+        }
+
+        for (piece, hold, next, held) in self.choices(node) {
+            for mut placement in movegen::placements(&node.board, &piece) {
+                let child = match self.place(node, &piece, &placement, hold, next) {
+                    Some(child) => child,
+                    None => continue,
+                };
+                if held {
+                    placement.actions.insert(0, Action::Hold);
+                }
+                let cases = self.cases.len();
+                self.cases
+                    .extend(piece_cases(&piece, &placement, &node.origins));
+                self.placements.push(placement);
+                if self.search(&child) {
+                    return true;
+                }
+                self.placements.pop();
+                self.cases.truncate(cases);
+                if self.timed_out {
+                    return false;
+                }
+            }
+        }
+        self.failed.insert(key);
+        false
+    }
+
+    /// The pieces that can be placed next, with the hold and next piece that follow, and
+    /// whether the hold is used.
+    fn choices(&self, node: &Node) -> Vec<(Tetromino, Option<u8>, usize, bool)> {
+        let mut choices = Vec::new();
+        let next = match self.pieces.get(node.next) {
+            Some(next) => *next,
+            None => return choices,
+        };
+        let piece = if node.next == 0 {
+            self.current.clone()
+        } else {
+            spawn(next)
+        };
+        choices.push((piece, node.hold, node.next + 1, false));
+        if node.can_hold {
+            match node.hold {
+                Some(held) if held != next => {
+                    choices.push((spawn(held), Some(next), node.next + 1, true))
+                }
+                None if node.next + 1 < self.pieces.len() => choices.push((
+                    spawn(self.pieces[node.next + 1]),
+                    Some(next),
+                    node.next + 2,
+                    true,
+                )),
+                _ => {}
+            }
+        }
+        choices
+    }
+
+    /// The node following `placement`, if the piece stays within the lines to clear and
+    /// leaves room for the pieces left.
+    fn place(
+        &self,
+        node: &Node,
+        piece: &Tetromino,
+        placement: &Placement,
+        hold: Option<u8>,
+        next: usize,
+    ) -> Option<Node> {
+        let rows = node.board.len();
+        let mut board = bot::lock_piece(
+            &node.board,
+            piece,
+            placement.state as usize,
+            placement.x,
+            placement.y,
+        );
+        if board[..rows - node.lines]
+            .iter()
+            .any(|row| row.iter().any(|case| *case != 0))
+        {
+            return None;
+        }
+        let mut origins = node.origins.clone();
+        let mut y = rows;
+        while y > 0 {
+            y -= 1;
+            if !board[y].contains(&0) {
+                board.remove(y);
+                origins.remove(y);
+            }
+        }
+        let cleared = rows - board.len();
+        let columns = board[0].len();
+        for _ in 0..cleared {
+            board.insert(0, vec![0; columns]);
+            origins.insert(0, None);
+        }
+
+        let lines = node.lines - cleared;
+        let empty = board[rows - lines..]
+            .iter()
+            .map(|row| row.iter().filter(|case| **case == 0).count())
+            .sum::<usize>();
+        let pieces_left = self.pieces.len() - next + hold.is_some() as usize;
+        if empty / 4 > pieces_left || !can_be_filled(&board[rows - lines..]) {
+            return None;
+        }
+        Some(Node {
+            board,
+            origins,
+            lines,
+            next,
+            hold,
+            can_hold: true,
+        })
+    }
+}
+
+/// Whether whole pieces could fill the empty cases of `region`. Cases of a column can end up
+/// next to each other once the rows between them are cleared, so the region only splits
+/// between two columns that have no empty case on the same row, and every part of it needs a
+/// multiple of four empty cases.
+fn can_be_filled(region: &[Vec<u8>]) -> bool {
+    let columns = region.first().map_or(0, Vec::len);
+    let mut part = 0;
+    for x in 0..columns {
+        part += region.iter().filter(|row| row[x] == 0).count();
+        let split = x + 1 == columns || !region.iter().any(|row| row[x] == 0 && row[x + 1] == 0);
+        if split {
+            if part % 4 != 0 {
+                return false;
+            }
+            part = 0;
+        }
+    }
+    true
+}
+
+fn spawn(kind: u8) -> Tetromino {
+    tetromino::from_kind(kind).expect("Piece kinds go from 1 to 7")
+}
+
+/// Cases of `piece` locked as in `placement`, on the rows of the searched board.
+fn piece_cases(
+    piece: &Tetromino,
+    placement: &Placement,
+    origins: &[Option<usize>],
+) -> Vec<(usize, usize, u8)> {
+    let mut cases = Vec::new();
+    for (line_num, line) in piece.states[placement.state as usize].iter().enumerate() {
+        for (case_num, case) in line.iter().enumerate() {
+            if *case == 0 {
+                continue;
+            }
+            let x = placement.x + case_num as isize;
+            if let Some(Some(y)) = origins.get(placement.y + line_num) {
+                cases.push((*y, x as usize, *case));
+            }
+        }
+    }
+    cases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+    use crate::game::LineClear;
+
+    /// A game on `rows` with `current` then `queue` to play.
+    fn game(rows: &str, current: u8, queue: &[u8]) -> Game {
+        let mut game = Game::with_seed(0);
+        board::load(&board::parse(rows).unwrap(), &mut game).unwrap();
+        game.set_queue(queue);
+        game.current_piece = tetromino::from_kind(current);
+        game
+    }
+
+    /// Plays the actions of `solution` on `game`, spawning pieces as they are needed, and
+    /// returns the last lock.
+    fn replay(game: &mut Game, solution: &Solution) -> Option<LineClear> {
+        let mut last = None;
+        for placement in &solution.placements {
+            if game.current_piece.is_none() {
+                game.current_piece = Some(game.create_new_tetromino());
+            }
+            for action in &placement.actions {
+                if let Some(clear) = game.apply_action(*action) {
+                    last = Some(clear);
+                }
+            }
+        }
+        last
+    }
+
+    const BUDGET: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn finds_the_perfect_clear_of_a_well() {
+        let well = "GGG....GGG\nGGG....GGG\nGGG....GGG\nGGG....GGG";
+        let mut game = game(well, 3, &[2, 4, 1]);
+        let solution = find(&game, PC_LINES, BUDGET).unwrap();
+        assert_eq!(solution.placements.len(), 4);
+        let height = game.game_map.len();
+        assert!(solution.board[height - 4..]
+            .iter()
+            .all(|row| row[3..7].iter().all(|case| *case != 0)));
+        assert!(replay(&mut game, &solution).unwrap().all_clear);
+        assert!(game.game_map.iter().flatten().all(|case| *case == 0));
+    }
+
+    #[test]
+    fn gaps_that_pieces_cant_fill_have_no_perfect_clear() {
+        let game = game("GGGGGGGGG.", 1, &[2, 3, 4, 5, 6, 7]);
+        assert_eq!(find(&game, PC_LINES, BUDGET), None);
+    }
+
+    #[test]
+    fn holds_the_current_piece_when_the_next_one_clears() {
+        let mut game = game("GGGGGG....", 4, &[1]);
+        let solution = find(&game, PC_LINES, BUDGET).unwrap();
+        assert_eq!(solution.placements.len(), 1);
+        assert_eq!(solution.placements[0].actions[0], Action::Hold);
+        assert!(replay(&mut game, &solution).unwrap().all_clear);
+        assert_eq!(game.hold.as_ref().map(Tetromino::kind), Some(4));
+    }
+}
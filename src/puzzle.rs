@@ -0,0 +1,346 @@
+//! Puzzles: a starting board and a fixed sequence of pieces to reach a goal with, such as
+//! clearing some lines, a T-spin or a perfect clear. They are read from files like this
+//! one, the board being a text board (see `board`) or the first page of a fumen:
+//!
+//! ```text
+//! name: T-spin single
+//! goal: tss
+//! pieces: T
+//! hold: I
+//! board:
+//! ..........
+//! GG..GGGGGG
+//! GG...GGGGG
+//! GGG.GGGGGG
+//! ```
+//!
+//! Goals are `lines N`, `tss`, `tsd`, `tst` and `pc`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::board;
+use crate::files;
+use crate::fumen;
+use crate::game::{Action, Game, LineClear};
+use crate::tetromino;
+
+/// Extension of puzzle files.
+pub const EXTENSION: &str = "puzzle";
+/// File listing the puzzles solved so far, one per line.
+pub const PROGRESS_FILE: &str = "puzzles.txt";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    /// Clear at least this many lines.
+    Lines(u32),
+    /// Clear this many lines at once with a T-spin.
+    TSpin(u32),
+    PerfectClear,
+}
+
+impl Goal {
+    fn parse(text: &str) -> Result<Goal, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match words[..] {
+            ["lines", lines] => lines
+                .parse()
+                .map(Goal::Lines)
+                .map_err(|_| format!("Invalid number of lines: {}", lines)),
+            ["tss"] => Ok(Goal::TSpin(1)),
+            ["tsd"] => Ok(Goal::TSpin(2)),
+            ["tst"] => Ok(Goal::TSpin(3)),
+            ["pc"] => Ok(Goal::PerfectClear),
+            _ => Err(format!("Unknown goal: {}", text)),
+        }
+    }
+
+    /// The goal as shown to the player.
+    pub fn describe(&self) -> String {
+        match *self {
+            Goal::Lines(1) => "CLEAR 1 LINE".to_owned(),
+            Goal::Lines(lines) => format!("CLEAR {} LINES", lines),
+            Goal::TSpin(1) => "T-SPIN SINGLE".to_owned(),
+            Goal::TSpin(2) => "T-SPIN DOUBLE".to_owned(),
+            Goal::TSpin(3) => "T-SPIN TRIPLE".to_owned(),
+            Goal::TSpin(lines) => format!("T-SPIN OF {} LINES", lines),
+            Goal::PerfectClear => "PERFECT CLEAR".to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    /// Name of the file without its extension, which tells puzzles apart.
+    pub id: String,
+    pub name: String,
+    pub goal: Goal,
+    /// Rows at the bottom of the board, from top to bottom.
+    pub board: Vec<Vec<u8>>,
+    /// Kinds of the pieces to play, in order.
+    pub pieces: Vec<u8>,
+    pub hold: Option<u8>,
+}
+
+impl Puzzle {
+    /// Reads the puzzle `id` written in `text`.
+    pub fn parse(id: &str, text: &str) -> Result<Puzzle, String> {
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = None;
+        let mut hold = None;
+        let mut board = None;
+        let mut lines = text.lines();
+        while let Some(line) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = files::key_value(line)?;
+            match key {
+                "name" => name = Some(value.to_owned()),
+                "goal" => goal = Some(Goal::parse(value)?),
+                "pieces" => pieces = Some(kinds(value)?),
+                "hold" => match kinds(value)?[..] {
+                    [kind] => hold = Some(kind),
+                    _ => return Err(format!("The hold must be one piece: {}", value)),
+                },
+                "fumen" => board = Some(fumen::trim(&fumen::decode(value)?[0].field)),
+                // The board takes the rest of the file.
+                "board" => {
+                    board = Some(board::parse(
+                        &lines.by_ref().collect::<Vec<_>>().join("\n"),
+                    )?)
+                }
+                _ => return Err(format!("Unknown key: {}", key)),
+            }
+        }
+
+        let puzzle = Puzzle {
+            id: id.to_owned(),
+            name: name.unwrap_or_else(|| id.to_owned()),
+            goal: goal.ok_or("The puzzle has no goal")?,
+            board: board.ok_or("The puzzle has no board")?,
+            pieces: pieces
+                .filter(|pieces| !pieces.is_empty())
+                .ok_or("The puzzle has no pieces")?,
+            hold,
+        };
+        board::load(&puzzle.board, &mut Game::with_seed(0))?;
+        Ok(puzzle)
+    }
+}
+
+/// The kinds of the pieces written in `letters`, spaces left out.
+fn kinds(letters: &str) -> Result<Vec<u8>, String> {
+    letters
+        .chars()
+        .filter(|letter| !letter.is_whitespace())
+        .map(|letter| {
+            board::case(letter)
+                .filter(|case| (1..=7).contains(case))
+                .ok_or_else(|| format!("Unknown piece: {}", letter))
+        })
+        .collect()
+}
+
+/// The puzzles of the files of `dir`, sorted by file name.
+pub fn load_dir(dir: &Path) -> Result<Vec<Puzzle>, String> {
+    files::load_files(dir, EXTENSION, Puzzle::parse)
+}
+
+/// Ids of the puzzles solved so far, read from `path`. Empty until the file gets written.
+pub fn load_progress(path: &Path) -> BTreeSet<String> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+pub fn save_progress(path: &Path, solved: &BTreeSet<String>) -> io::Result<()> {
+    let mut text = String::new();
+    for id in solved {
+        text.push_str(id);
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
+
+/// A try at a puzzle: a game whose pieces are the ones of the puzzle and nothing else.
+#[derive(Clone)]
+pub struct Attempt {
+    pub game: Game,
+    pub goal: Goal,
+    /// Pieces of the puzzle still in the queue of the game, at its front.
+    remaining: usize,
+    /// Lines cleared so far.
+    pub lines: u32,
+    /// Whether the goal was reached, once the attempt is over.
+    pub solved: Option<bool>,
+}
+
+impl Attempt {
+    /// Starts `puzzle`, its first piece spawned.
+    pub fn new(puzzle: &Puzzle) -> Attempt {
+        let mut game = Game::with_seed(0);
+        board::load(&puzzle.board, &mut game).expect("Puzzle boards fit on the board");
+        game.queue = puzzle
+            .pieces
+            .iter()
+            .filter_map(|kind| tetromino::from_kind(*kind))
+            .collect();
+        game.hold = puzzle.hold.and_then(tetromino::from_kind);
+        let mut attempt = Attempt {
+            game,
+            goal: puzzle.goal,
+            remaining: puzzle.pieces.len(),
+            lines: 0,
+            solved: None,
+        };
+        attempt.spawn();
+        attempt
+    }
+
+    /// Plays `actions`, then spawns the next piece if the current one got locked. The attempt
+    /// is over as soon as the goal is reached, or once the pieces run out without reaching it.
+    pub fn apply_actions(&mut self, actions: &[Action]) -> Vec<LineClear> {
+        let mut clears = Vec::new();
+        for action in actions {
+            if self.solved.is_some() {
+                break;
+            }
+            let held = self.game.hold.is_some();
+            // Holding with an empty hold takes a piece from the queue, which must be one of
+            // the puzzle.
+            if *action == Action::Hold && !held && self.remaining == 0 {
+                continue;
+            }
+            if let Some(clear) = self.game.apply_action(*action) {
+                self.lines += clear.lines;
+                let reached = match self.goal {
+                    Goal::Lines(lines) => self.lines >= lines,
+                    Goal::TSpin(lines) => clear.t_spin && clear.lines == lines,
+                    Goal::PerfectClear => clear.all_clear,
+                };
+                if reached {
+                    self.solved = Some(true);
+                }
+                clears.push(clear);
+            }
+            if !held && self.game.hold.is_some() {
+                self.take();
+            }
+        }
+        self.spawn();
+        clears
+    }
+
+    /// Number of pieces left to play, the current one included.
+    pub fn pieces_left(&self) -> usize {
+        self.remaining + self.game.current_piece.is_some() as usize
+    }
+
+    /// Spawns the next piece if there is no current one, ending the attempt without any.
+    fn spawn(&mut self) {
+        if self.solved.is_some() || self.game.current_piece.is_some() {
+            return;
+        }
+        if self.remaining == 0 {
+            self.solved = Some(false);
+            return;
+        }
+        let piece = self.game.create_new_tetromino();
+        self.take();
+        if piece.test_current_position(&self.game.game_map) {
+            self.game.current_piece = Some(piece);
+        } else {
+            self.solved = Some(false);
+        }
+    }
+
+    /// Accounts for a piece taken from the queue, dropping the random one the game added.
+    fn take(&mut self) {
+        self.remaining -= 1;
+        self.game.queue.truncate(self.remaining);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen;
+
+    /// A puzzle with `pieces` to clear four lines of a well on the right.
+    fn well(pieces: &str) -> Puzzle {
+        let text = format!(
+            "goal: lines 4\npieces: {}\nboard:\n{}",
+            pieces,
+            ["GGGGGGGGG."; 4].join("\n")
+        );
+        Puzzle::parse("well", &text).unwrap()
+    }
+
+    /// Actions dropping the current piece of `attempt` upright into the well.
+    fn into_the_well(attempt: &Attempt) -> Vec<Action> {
+        let piece = attempt.game.current_piece.as_ref().unwrap();
+        movegen::placements(&attempt.game.game_map, piece)
+            .into_iter()
+            .find(|placement| {
+                movegen::cases(piece, placement.state, placement.x, placement.y)
+                    .iter()
+                    .all(|&(_, x)| x == 9)
+            })
+            .unwrap()
+            .actions
+    }
+
+    #[test]
+    fn hold_takes_exactly_one_piece() {
+        assert_eq!(well("I").hold, None);
+        let text = "goal: pc\npieces: I\nhold: T\nboard:\n..........";
+        assert_eq!(Puzzle::parse("hold", text).unwrap().hold, Some(7));
+        assert!(Puzzle::parse("hold", &text.replace("T", "TO")).is_err());
+        assert!(Puzzle::parse("hold", &text.replace(" T", "")).is_err());
+    }
+
+    #[test]
+    fn holding_into_an_empty_hold_uses_a_piece() {
+        let mut attempt = Attempt::new(&well("O I T"));
+        assert_eq!(attempt.pieces_left(), 3);
+
+        attempt.apply_actions(&[Action::Hold]);
+        assert_eq!(attempt.game.current_piece.as_ref().unwrap().kind(), 1);
+        assert_eq!(attempt.game.hold.as_ref().unwrap().kind(), 4);
+        assert_eq!(attempt.pieces_left(), 2);
+        assert_eq!(attempt.game.queue.len(), 1);
+        assert_eq!(attempt.solved, None);
+    }
+
+    #[test]
+    fn holding_is_refused_without_pieces_left() {
+        let mut attempt = Attempt::new(&well("I"));
+        attempt.apply_actions(&[Action::Hold]);
+        assert_eq!(attempt.game.current_piece.as_ref().unwrap().kind(), 1);
+        assert!(attempt.game.hold.is_none());
+        assert_eq!(attempt.pieces_left(), 1);
+    }
+
+    #[test]
+    fn the_last_piece_solves_the_puzzle() {
+        let mut attempt = Attempt::new(&well("I"));
+        let actions = into_the_well(&attempt);
+        attempt.apply_actions(&actions);
+        assert_eq!(attempt.lines, 4);
+        assert_eq!(attempt.solved, Some(true));
+    }
+
+    #[test]
+    fn the_last_piece_fails_the_puzzle() {
+        let mut attempt = Attempt::new(&well("I"));
+        attempt.apply_actions(&[Action::HardDrop]);
+        assert_eq!(attempt.lines, 0);
+        assert_eq!(attempt.pieces_left(), 0);
+        assert_eq!(attempt.solved, Some(false));
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::events::{self, KeyMap};
+use crate::render::Renderer;
+use tetrs::puzzle::{self, Attempt, Puzzle};
+
+/// Lists the puzzles of `dir` and plays the one the player picks, until the player quits.
+/// Solved puzzles are saved in `puzzle::PROGRESS_FILE`.
+pub fn run_puzzles(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, dir: &str) {
+    let puzzles = match puzzle::load_dir(Path::new(dir)) {
+        Ok(puzzles) => puzzles,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if puzzles.is_empty() {
+        eprintln!("No .{} file in {}", puzzle::EXTENSION, dir);
+        return;
+    }
+    let progress_file = Path::new(puzzle::PROGRESS_FILE);
+    let mut solved = puzzle::load_progress(progress_file);
+    let mut selected = puzzles
+        .iter()
+        .position(|puzzle| !solved.contains(&puzzle.id))
+        .unwrap_or(0);
+
+    loop {
+        let items: Vec<String> = puzzles
+            .iter()
+            .map(|puzzle| {
+                let done = if solved.contains(&puzzle.id) {
+                    "  DONE"
+                } else {
+                    ""
+                };
+                format!("{}{}", puzzle.name.to_uppercase(), done)
+            })
+            .collect();
+        let count = puzzles
+            .iter()
+            .filter(|puzzle| solved.contains(&puzzle.id))
+            .count();
+        let title = format!("PUZZLES: {}/{} SOLVED", count, puzzles.len());
+        let mut play = crate::display_menu(renderer, event_pump, &title, &items, &mut selected);
+        if !play {
+            return;
+        }
+
+        // Solving a puzzle moves on to the next one until the player goes back to the menu.
+        while play {
+            let puzzle = &puzzles[selected];
+            match play_puzzle(renderer, event_pump, puzzle) {
+                Some(true) => {
+                    solved.insert(puzzle.id.clone());
+                    if let Err(e) = puzzle::save_progress(progress_file, &solved) {
+                        eprintln!("Failed to save {}: {}", puzzle::PROGRESS_FILE, e);
+                    }
+                    let lines = vec![
+                        "SOLVED".to_owned(),
+                        puzzle.name.to_uppercase(),
+                        "ENTER: NEXT PUZZLE".to_owned(),
+                        "ESCAPE: MENU".to_owned(),
+                    ];
+                    play = crate::display_end_screen(renderer, event_pump, &lines)
+                        && selected + 1 < puzzles.len();
+                    if play {
+                        selected += 1;
+                    }
+                }
+                Some(false) => {
+                    let lines = vec![
+                        "FAILED".to_owned(),
+                        puzzle.goal.describe(),
+                        "ENTER: RETRY".to_owned(),
+                        "ESCAPE: MENU".to_owned(),
+                    ];
+                    play = crate::display_end_screen(renderer, event_pump, &lines);
+                }
+                None => play = false,
+            }
+        }
+    }
+}
+
+/// Plays `puzzle` until it is solved (true), failed (false) or the player quits (None).
+/// Backspace starts it over and F prints the fumen of the board.
+fn play_puzzle(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    puzzle: &Puzzle,
+) -> Option<bool> {
+    let mut attempt = Attempt::new(puzzle);
+    renderer.hint = None;
+    loop {
+        let mut quit = false;
+        let mut restart = false;
+        let mut export = false;
+        let actions = events::poll_actions(
+            event_pump,
+            &[KeyMap::single_player()],
+            &mut quit,
+            &mut restart,
+            &mut export,
+        );
+        if quit {
+            return None;
+        }
+        if restart {
+            attempt = Attempt::new(puzzle);
+        }
+        attempt.apply_actions(&actions[0]);
+        if export {
+            crate::print_fumens(&[&attempt.game]);
+        }
+        renderer.draw_boards(&[(&attempt.game, &info(puzzle, &attempt))]);
+        if attempt.solved.is_some() {
+            // Leave the last locked piece on screen for a moment.
+            sleep(Duration::from_millis(500));
+            return attempt.solved;
+        }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+fn info(puzzle: &Puzzle, attempt: &Attempt) -> Vec<String> {
+    vec![
+        puzzle.name.to_uppercase(),
+        format!("GOAL: {}", puzzle.goal.describe()),
+        format!("PIECES LEFT: {}", attempt.pieces_left()),
+        format!("LINES: {}", attempt.lines),
+        "BACKSPACE: RESTART".to_owned(),
+    ]
+}
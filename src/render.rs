@@ -0,0 +1,399 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas, Texture, TextureCreator};
+use sdl2::ttf::Font;
+use sdl2::video::{Window, WindowContext};
+
+use crate::game::Game;
+use crate::tetromino::Tetromino;
+
+pub const GAME_HEIGHT: usize = 40;
+/// Cases are smaller with several boards so that they fit side by side.
+const VERSUS_GAME_HEIGHT: u32 = 32;
+/// Opacity of the cases of an overlay, out of 255.
+const OVERLAY_ALPHA: u8 = 96;
+/// Color of each case value, the seven pieces then garbage.
+const COLORS: [(u8, u8, u8); 8] = [
+    (255, 69, 69),
+    (255, 220, 69),
+    (237, 150, 37),
+    (171, 99, 237),
+    (77, 149, 239),
+    (39, 218, 225),
+    (45, 216, 47),
+    (128, 128, 128),
+];
+
+pub struct Renderer<'a> {
+    pub canvas: Canvas<Window>,
+    pub texture_creator: &'a TextureCreator<WindowContext>,
+    pub font: &'a Font<'a, 'static>,
+    pub width: u32,
+    pub height: u32,
+    /// Cases outlined over the first board, such as the pieces of a perfect clear.
+    pub hint: Option<Vec<Vec<u8>>>,
+    /// Cases shaded over the first board, such as where the current piece of an opener goes.
+    pub overlay: Option<Vec<Vec<u8>>>,
+    grid: Texture<'a>,
+    border: Texture<'a>,
+    textures: Vec<Texture<'a>>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(
+        mut canvas: Canvas<Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        font: &'a Font<'a, 'static>,
+        width: u32,
+        height: u32,
+    ) -> Renderer<'a> {
+        let grid = create_texture_rect(
+            &mut canvas,
+            texture_creator,
+            0,
+            0,
+            0,
+            GAME_HEIGHT as u32 * 10,
+            GAME_HEIGHT as u32 * 16,
+        )
+        .expect("Failed to create grid");
+
+        let border = create_texture_rect(
+            &mut canvas,
+            texture_creator,
+            255,
+            255,
+            255,
+            GAME_HEIGHT as u32 * 10 + 20,
+            GAME_HEIGHT as u32 * 16 + 20,
+        )
+        .expect("Failed to create border");
+
+        macro_rules! texture {
+            ($r:expr, $g:expr, $b:expr) => {
+                create_texture_rect(
+                    &mut canvas,
+                    texture_creator,
+                    $r,
+                    $g,
+                    $b,
+                    GAME_HEIGHT as u32,
+                    GAME_HEIGHT as u32,
+                )
+                .unwrap()
+            };
+        }
+
+        let textures = COLORS.iter().map(|&(r, g, b)| texture!(r, g, b)).collect();
+
+        Renderer {
+            canvas,
+            texture_creator,
+            font,
+            width,
+            height,
+            hint: None,
+            overlay: None,
+            grid,
+            border,
+            textures,
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    pub fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    /// Draws the board of `game` and its current piece, the top-left corner of the grid
+    /// being at (`grid_x`, `grid_y`) and every case `case_size` pixels wide.
+    pub fn draw_game(&mut self, game: &Game, grid_x: i32, grid_y: i32, case_size: u32) {
+        let columns = game.game_map[0].len() as u32;
+        let rows = game.game_map.len() as u32;
+        let border_size = case_size / 4;
+
+        self.canvas
+            .copy(
+                &self.border,
+                None,
+                Rect::new(
+                    grid_x - border_size as i32,
+                    grid_y - border_size as i32,
+                    case_size * columns + border_size * 2,
+                    case_size * rows + border_size * 2,
+                ),
+            )
+            .expect("Couldn't copy texture into window");
+        self.canvas
+            .copy(
+                &self.grid,
+                None,
+                Rect::new(grid_x, grid_y, case_size * columns, case_size * rows),
+            )
+            .expect("Couldn't copy texture into window");
+
+        for (line_num, line) in game.game_map.iter().enumerate() {
+            for (case_num, case) in line.iter().enumerate() {
+                self.draw_case(
+                    *case,
+                    grid_x + case_num as i32 * case_size as i32,
+                    grid_y + line_num as i32 * case_size as i32,
+                    case_size,
+                );
+            }
+        }
+
+        if let Some(ref piece) = game.current_piece {
+            for (line_num, line) in piece.states[piece.current_state as usize]
+                .iter()
+                .enumerate()
+            {
+                for (case_num, case) in line.iter().enumerate() {
+                    self.draw_case(
+                        *case,
+                        grid_x + (piece.x + case_num as isize) as i32 * case_size as i32,
+                        grid_y + (piece.y + line_num) as i32 * case_size as i32,
+                        case_size,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draws `piece` in its spawn orientation, the top-left corner of its box being at
+    /// (`x`, `y`).
+    pub fn draw_piece(&mut self, piece: &Tetromino, x: i32, y: i32, case_size: u32) {
+        for (line_num, line) in piece.states[0].iter().enumerate() {
+            for (case_num, case) in line.iter().enumerate() {
+                self.draw_case(
+                    *case,
+                    x + case_num as i32 * case_size as i32,
+                    y + line_num as i32 * case_size as i32,
+                    case_size,
+                );
+            }
+        }
+    }
+
+    /// Draws the held piece on the left of the board drawn at (`grid_x`, `grid_y`) and the
+    /// next pieces on its right, at half the size of the board cases.
+    pub fn draw_hold_and_queue(&mut self, game: &Game, grid_x: i32, grid_y: i32, case_size: u32) {
+        let small_size = case_size / 2;
+        let border_size = case_size as i32 / 4;
+        let hold_x = grid_x - border_size - small_size as i32 * 4 - 10;
+        self.draw_text("HOLD", hold_x, grid_y);
+        if let Some(ref piece) = game.hold {
+            self.draw_piece(piece, hold_x, grid_y + 40, small_size);
+        }
+
+        let queue_x = grid_x + case_size as i32 * game.game_map[0].len() as i32 + border_size + 10;
+        self.draw_text("NEXT", queue_x, grid_y);
+        for (i, piece) in game.queue.iter().enumerate() {
+            let y = grid_y + 40 + i as i32 * small_size as i32 * 3;
+            self.draw_piece(piece, queue_x, y, small_size);
+        }
+    }
+
+    /// Draws every board side by side with its hold piece, next pieces and text lines, and
+    /// shows the result. A lone board is drawn bigger, with its text on the right.
+    pub fn draw_boards(&mut self, boards: &[(&Game, &[String])]) {
+        let case_size = if boards.len() > 1 {
+            VERSUS_GAME_HEIGHT
+        } else {
+            GAME_HEIGHT as u32
+        };
+        let board_width = case_size as i32 * 10;
+        let board_height = case_size as i32 * 16;
+        let region_width = self.width as i32 / boards.len().max(1) as i32;
+
+        self.clear(Color::RGB(255, 0, 0));
+        for (i, (game, texts)) in boards.iter().enumerate() {
+            let grid_x = region_width * i as i32 + (region_width - board_width) / 2;
+            if boards.len() > 1 {
+                let grid_y = (self.height as i32 - board_height) / 2 - 40;
+                self.draw_game(game, grid_x, grid_y, case_size);
+                self.draw_hold_and_queue(game, grid_x, grid_y, case_size);
+                self.draw_text_lines(texts, grid_x, grid_y + board_height + 25, 35);
+            } else {
+                let grid_y = (self.height as i32 - board_height) / 2;
+                self.draw_game(game, grid_x, grid_y, case_size);
+                if let Some(overlay) = self.overlay.take() {
+                    self.draw_overlay(&overlay, game, grid_x, grid_y, case_size);
+                    self.overlay = Some(overlay);
+                }
+                if let Some(hint) = self.hint.take() {
+                    self.draw_hint(&hint, game, grid_x, grid_y, case_size);
+                    self.hint = Some(hint);
+                }
+                self.draw_hold_and_queue(game, grid_x, grid_y, case_size);
+                self.draw_text_lines(texts, grid_x + board_width + 15, grid_y + 360, 35);
+            }
+        }
+        self.present();
+    }
+
+    /// The row and column of the case under the pixel (`x`, `y`) when `draw_boards` draws
+    /// `game` alone.
+    pub fn case_at(&self, game: &Game, x: i32, y: i32) -> Option<(usize, usize)> {
+        let case_size = GAME_HEIGHT as i32;
+        let columns = game.game_map[0].len() as i32;
+        let rows = game.game_map.len() as i32;
+        let grid_x = (self.width as i32 - case_size * columns) / 2;
+        let grid_y = (self.height as i32 - case_size * rows) / 2;
+        if x < grid_x || y < grid_y {
+            return None;
+        }
+        let column = (x - grid_x) / case_size;
+        let row = (y - grid_y) / case_size;
+        if column >= columns || row >= rows {
+            return None;
+        }
+        Some((row as usize, column as usize))
+    }
+
+    /// Outlines the cases of `hint` that are still empty on the board of `game`.
+    fn draw_hint(
+        &mut self,
+        hint: &[Vec<u8>],
+        game: &Game,
+        grid_x: i32,
+        grid_y: i32,
+        case_size: u32,
+    ) {
+        for (line_num, line) in hint.iter().enumerate() {
+            for (case_num, case) in line.iter().enumerate() {
+                if *case == 0 || game.game_map[line_num][case_num] != 0 {
+                    continue;
+                }
+                let (r, g, b) = COLORS[*case as usize - 1];
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+                let x = grid_x + case_num as i32 * case_size as i32;
+                let y = grid_y + line_num as i32 * case_size as i32;
+                for inset in 2..5 {
+                    self.canvas
+                        .draw_rect(Rect::new(
+                            x + inset,
+                            y + inset,
+                            case_size - inset as u32 * 2,
+                            case_size - inset as u32 * 2,
+                        ))
+                        .expect("Couldn't draw hint");
+                }
+            }
+        }
+    }
+
+    /// Fills the cases of `overlay` that are still empty on the board of `game` with their
+    /// color, translucent.
+    fn draw_overlay(
+        &mut self,
+        overlay: &[Vec<u8>],
+        game: &Game,
+        grid_x: i32,
+        grid_y: i32,
+        case_size: u32,
+    ) {
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        for (line_num, line) in overlay.iter().enumerate() {
+            for (case_num, case) in line.iter().enumerate() {
+                if *case == 0 || game.game_map[line_num][case_num] != 0 {
+                    continue;
+                }
+                let (r, g, b) = COLORS[*case as usize - 1];
+                self.canvas
+                    .set_draw_color(Color::RGBA(r, g, b, OVERLAY_ALPHA));
+                self.canvas
+                    .fill_rect(Rect::new(
+                        grid_x + case_num as i32 * case_size as i32,
+                        grid_y + line_num as i32 * case_size as i32,
+                        case_size,
+                        case_size,
+                    ))
+                    .expect("Couldn't draw overlay");
+            }
+        }
+        self.canvas.set_blend_mode(BlendMode::None);
+    }
+
+    fn draw_case(&mut self, case: u8, x: i32, y: i32, case_size: u32) {
+        if case == 0 {
+            return;
+        }
+        self.canvas
+            .copy(
+                &self.textures[case as usize - 1],
+                None,
+                Rect::new(x, y, case_size, case_size),
+            )
+            .expect("Couldn't copy texture to canvas");
+    }
+
+    /// Draws one line of text per entry of `lines`, starting at (`x`, `y`).
+    pub fn draw_text_lines(&mut self, lines: &[String], x: i32, y: i32, spacing: i32) {
+        for (i, line) in lines.iter().enumerate() {
+            self.draw_text(line, x, y + spacing * i as i32);
+        }
+    }
+
+    /// Same as `draw_text_lines` but every line is horizontally centered in the window.
+    pub fn draw_centered_lines(&mut self, lines: &[String], y: i32, spacing: i32) {
+        for (i, line) in lines.iter().enumerate() {
+            let x = (self.width as i32 - line.len() as i32 * 20) / 2;
+            self.draw_text(line, x, y + spacing * i as i32);
+        }
+    }
+
+    pub fn draw_text(&mut self, text: &str, x: i32, y: i32) {
+        let texture =
+            create_texture_from_text(self.texture_creator, self.font, text, 255, 255, 255)
+                .expect("Cannot render text");
+        self.canvas
+            .copy(&texture, None, get_rect_from_text(text, x, y))
+            .expect("couldn't copy text");
+    }
+}
+
+fn create_texture_from_text<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    font: &sdl2::ttf::Font,
+    text: &str,
+    r: u8,
+    g: u8,
+    b: u8,
+) -> Option<Texture<'a>> {
+    if let Ok(surface) = font.render(text).blended(Color::RGB(r, g, b)) {
+        texture_creator.create_texture_from_surface(&surface).ok()
+    } else {
+        None
+    }
+}
+
+fn get_rect_from_text(text: &str, x: i32, y: i32) -> Option<Rect> {
+    Some(Rect::new(x, y, text.len() as u32 * 20, 30))
+}
+
+fn create_texture_rect<'a>(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    r: u8,
+    g: u8,
+    b: u8,
+    width: u32,
+    height: u32,
+) -> Option<Texture<'a>> {
+    if let Ok(mut square_texture) = texture_creator.create_texture_target(None, width, height) {
+        canvas
+            .with_texture_canvas(&mut square_texture, |texture| {
+                texture.set_draw_color(Color::RGB(r, g, b));
+                texture.clear();
+            })
+            .expect("Failed to color a texture");
+        Some(square_texture)
+    } else {
+        None
+    }
+}
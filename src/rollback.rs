@@ -0,0 +1,454 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::battle::{Battle, Outcome};
+use crate::game::Action;
+use crate::net::{invalid_data, Connection, Message, Session, Settings};
+
+/// How many frames the simulation may run ahead of the last confirmed remote inputs before it
+/// waits for them.
+pub const MAX_ROLLBACK: u32 = 30;
+
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// A message channel whose receiving side never blocks.
+pub trait Transport {
+    fn send(&mut self, message: &Message) -> io::Result<()>;
+    /// Returns the next message if one already arrived.
+    fn try_receive(&mut self) -> io::Result<Option<Message>>;
+}
+
+/// Reads the messages of a connection on a background thread.
+pub struct TcpTransport {
+    writer: TcpStream,
+    messages: Receiver<io::Result<Message>>,
+}
+
+impl TcpTransport {
+    pub fn new(connection: Connection) -> TcpTransport {
+        let (mut reader, writer) = connection.into_parts();
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || loop {
+            let message = Message::read_from(&mut reader);
+            let failed = message.is_err();
+            if sender.send(message).is_err() || failed {
+                break;
+            }
+        });
+        TcpTransport { writer, messages }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        message.write_to(&mut self.writer)
+    }
+
+    fn try_receive(&mut self) -> io::Result<Option<Message>> {
+        match self.messages.try_recv() {
+            Ok(message) => message.map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection closed",
+            )),
+        }
+    }
+}
+
+impl Drop for TcpTransport {
+    /// Unblocks the reading thread.
+    fn drop(&mut self) {
+        let _ = self.writer.shutdown(Shutdown::Both);
+    }
+}
+
+/// In-process transport delivering every message after a fixed latency plus a random jitter,
+/// which can reorder them. Used to exercise rollbacks without a network.
+pub struct LatencyChannel {
+    sender: Sender<(Instant, Message)>,
+    receiver: Receiver<(Instant, Message)>,
+    in_flight: Vec<(Instant, Message)>,
+    latency: Duration,
+    jitter: Duration,
+    rng: StdRng,
+}
+
+impl LatencyChannel {
+    /// Creates both ends of a channel.
+    pub fn pair(
+        latency: Duration,
+        jitter: Duration,
+        seed: u64,
+    ) -> (LatencyChannel, LatencyChannel) {
+        let (first_sender, second_receiver) = mpsc::channel();
+        let (second_sender, first_receiver) = mpsc::channel();
+        let end = |sender, receiver, seed| LatencyChannel {
+            sender,
+            receiver,
+            in_flight: Vec::new(),
+            latency,
+            jitter,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        (
+            end(first_sender, first_receiver, seed),
+            end(second_sender, second_receiver, seed.wrapping_add(1)),
+        )
+    }
+}
+
+impl Transport for LatencyChannel {
+    fn send(&mut self, message: &Message) -> io::Result<()> {
+        let delay = self.latency + self.jitter.mul_f64(self.rng.gen());
+        self.sender
+            .send((Instant::now() + delay, message.clone()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "channel closed"))
+    }
+
+    fn try_receive(&mut self) -> io::Result<Option<Message>> {
+        self.in_flight.extend(self.receiver.try_iter());
+        let now = Instant::now();
+        let next = self
+            .in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, (arrival, _))| *arrival <= now)
+            .min_by_key(|(_, (arrival, _))| *arrival)
+            .map(|(index, _)| index);
+        Ok(next.map(|index| self.in_flight.remove(index).1))
+    }
+}
+
+/// A battle against a remote player that never waits for the network. The remote player is
+/// predicted to do nothing on frames whose inputs did not arrive yet; when they arrive and
+/// contradict the prediction, the battle goes back to the state saved before that frame and
+/// is simulated again up to the current frame.
+pub struct Rollback<T: Transport> {
+    battle: Battle,
+    local: usize,
+    transport: T,
+    input_delay: u32,
+    /// Next frame to simulate.
+    frame: u32,
+    /// First frame whose remote inputs are still unknown.
+    confirmed: u32,
+    /// Local actions, by frame.
+    local_inputs: Vec<Vec<Action>>,
+    /// Remote actions by frame, `None` until they arrive.
+    remote_inputs: Vec<Option<Vec<Action>>>,
+    /// State at the start of every frame from `confirmed` to `frame`.
+    snapshots: VecDeque<Battle>,
+    /// Earliest simulated frame whose prediction turned out wrong.
+    mispredicted: Option<u32>,
+    opponent_left: bool,
+    /// Number of times the battle had to be simulated again.
+    pub rollbacks: u32,
+}
+
+impl<T: Transport> Rollback<T> {
+    pub fn new(transport: T, local: usize, settings: Settings) -> Rollback<T> {
+        let battle = Battle::new(settings.seeds, settings.garbage_delay);
+        let delay = settings.input_delay as usize;
+        Rollback {
+            snapshots: vec![battle.clone()].into(),
+            battle,
+            local,
+            transport,
+            input_delay: settings.input_delay,
+            frame: 0,
+            confirmed: 0,
+            local_inputs: vec![Vec::new(); delay],
+            remote_inputs: vec![Some(Vec::new()); delay],
+            mispredicted: None,
+            opponent_left: false,
+            rollbacks: 0,
+        }
+    }
+
+    /// Handles the messages that arrived, rolling back if needed. Returns false if the
+    /// opponent left.
+    pub fn poll(&mut self) -> io::Result<bool> {
+        while let Some(message) = self.transport.try_receive()? {
+            match message {
+                Message::Inputs { frame, actions } => self.receive(frame, actions)?,
+                Message::Quit => self.opponent_left = true,
+                message => return Err(invalid_data(format!("unexpected {:?}", message))),
+            }
+        }
+        self.resimulate();
+        self.confirm();
+        Ok(!self.opponent_left)
+    }
+
+    /// Whether every frame simulated so far used the real remote inputs.
+    pub fn is_confirmed(&self) -> bool {
+        self.confirmed == self.frame
+    }
+
+    fn receive(&mut self, frame: u32, actions: Vec<Action>) -> io::Result<()> {
+        // The opponent stalls MAX_ROLLBACK frames past the last of our inputs it got, which is
+        // at most `input_delay` frames ahead of ours, then sends its own that far ahead.
+        let horizon = self
+            .frame
+            .saturating_add(2 * self.input_delay)
+            .saturating_add(MAX_ROLLBACK);
+        if frame > horizon {
+            return Err(invalid_data(format!(
+                "inputs for frame {} are too far ahead of frame {}",
+                frame, self.frame
+            )));
+        }
+        let index = frame as usize;
+        if frame < self.confirmed || matches!(self.remote_inputs.get(index), Some(Some(_))) {
+            return Err(invalid_data(format!(
+                "inputs for frame {} sent twice",
+                frame
+            )));
+        }
+        if self.remote_inputs.len() <= index {
+            self.remote_inputs.resize(index + 1, None);
+        }
+        if frame < self.frame && !actions.is_empty() {
+            self.mispredicted = Some(self.mispredicted.map_or(frame, |first| first.min(frame)));
+        }
+        self.remote_inputs[index] = Some(actions);
+        Ok(())
+    }
+
+    fn resimulate(&mut self) {
+        if let Some(first) = self.mispredicted.take() {
+            let start = (first - self.confirmed) as usize;
+            self.snapshots.truncate(start + 1);
+            self.battle = self.snapshots[start].clone();
+            for frame in first..self.frame {
+                self.simulate(frame);
+                self.snapshots.push_back(self.battle.clone());
+            }
+            self.rollbacks += 1;
+        }
+    }
+
+    /// Drops the snapshots nothing can roll back to anymore.
+    fn confirm(&mut self) {
+        while self.confirmed < self.frame
+            && matches!(
+                self.remote_inputs.get(self.confirmed as usize),
+                Some(Some(_))
+            )
+        {
+            self.confirmed += 1;
+            self.snapshots.pop_front();
+        }
+    }
+
+    fn simulate(&mut self, frame: u32) {
+        let local_actions = &self.local_inputs[frame as usize][..];
+        let remote_actions: &[Action] = match self.remote_inputs.get(frame as usize) {
+            Some(Some(actions)) => actions,
+            _ => &[],
+        };
+        if self.local == 0 {
+            self.battle.step([local_actions, remote_actions]);
+        } else {
+            self.battle.step([remote_actions, local_actions]);
+        }
+    }
+}
+
+impl<T: Transport> Session for Rollback<T> {
+    fn advance(&mut self, actions: Vec<Action>) -> io::Result<bool> {
+        self.transport.send(&Message::Inputs {
+            frame: self.frame + self.input_delay,
+            actions: actions.clone(),
+        })?;
+        self.local_inputs.push(actions);
+        if !self.poll()? {
+            return Ok(false);
+        }
+        while self.frame - self.confirmed >= MAX_ROLLBACK {
+            thread::sleep(Duration::from_millis(1));
+            if !self.poll()? {
+                return Ok(false);
+            }
+        }
+        self.simulate(self.frame);
+        self.frame += 1;
+        self.snapshots.push_back(self.battle.clone());
+        Ok(true)
+    }
+
+    fn battle(&self) -> &Battle {
+        &self.battle
+    }
+
+    /// Only the outcome of the confirmed state counts, a predicted top-out may not happen.
+    fn outcome(&self) -> Option<Outcome> {
+        self.snapshots.front().and_then(Battle::outcome)
+    }
+
+    fn local(&self) -> usize {
+        self.local
+    }
+
+    fn quit(&mut self) {
+        let _ = self.transport.send(&Message::Quit);
+    }
+}
+
+/// Plays two rollback sessions against each other for `frames` frames over a
+/// `LatencyChannel`, each on its own thread and pressing random keys. Checks that both peers
+/// end up in the state of a battle simulated directly with all the inputs, and returns the
+/// number of rollbacks of each peer.
+pub fn latency_test(
+    latency: Duration,
+    jitter: Duration,
+    settings: Settings,
+    frames: u32,
+) -> io::Result<[u32; 2]> {
+    let (first, second) = LatencyChannel::pair(latency, jitter, settings.seeds[0]);
+    let peer = |transport: LatencyChannel, local: usize| {
+        thread::spawn(move || -> io::Result<_> {
+            let mut session = Rollback::new(transport, local, settings);
+            let mut rng = StdRng::seed_from_u64(settings.seeds[local]);
+            let mut inputs = Vec::new();
+            for _ in 0..frames {
+                let actions = random_actions(&mut rng);
+                inputs.push(actions.clone());
+                session.advance(actions)?;
+                thread::sleep(FRAME);
+            }
+            // Wait for the last inputs of the other peer.
+            let deadline = Instant::now() + latency + jitter + Duration::from_secs(1);
+            while !session.is_confirmed() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(1));
+                session.poll()?;
+            }
+            if !session.is_confirmed() {
+                return Err(invalid_data(format!(
+                    "player {} only confirmed {} frames out of {}",
+                    local + 1,
+                    session.confirmed,
+                    frames
+                )));
+            }
+            Ok((session.battle.checksum(), session.rollbacks, inputs))
+        })
+    };
+    let peers = vec![peer(first, 0), peer(second, 1)];
+    let mut results = Vec::new();
+    for peer in peers {
+        let result = peer.join().map_err(|_| io::Error::other("peer panicked"))?;
+        results.push(result?);
+    }
+
+    let mut reference = Battle::new(settings.seeds, settings.garbage_delay);
+    let none = Vec::new();
+    for frame in 0..frames {
+        let input = |player: usize| match frame.checked_sub(settings.input_delay) {
+            Some(frame) => &results[player].2[frame as usize],
+            None => &none,
+        };
+        reference.step([input(0), input(1)]);
+    }
+    for (player, (checksum, _, _)) in results.iter().enumerate() {
+        if *checksum != reference.checksum() {
+            return Err(invalid_data(format!(
+                "player {} desynced from the reference battle",
+                player + 1
+            )));
+        }
+    }
+    Ok([results[0].1, results[1].1])
+}
+
+fn random_actions(rng: &mut StdRng) -> Vec<Action> {
+    const ACTIONS: [Action; 7] = [
+        Action::Left,
+        Action::Right,
+        Action::Left,
+        Action::Right,
+        Action::Rotate,
+        Action::SoftDrop,
+        Action::HardDrop,
+    ];
+    if rng.gen_bool(0.15) {
+        vec![ACTIONS[rng.gen_range(0, ACTIONS.len())]]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            seeds: [7, 8],
+            garbage_delay: 60,
+            input_delay: 2,
+        }
+    }
+
+    /// Plays `frames` frames of random keys over `transport`, then waits for the last inputs
+    /// of the other peer. Returns the checksum of the battle and the number of rollbacks.
+    fn play(transport: LatencyChannel, local: usize, frames: u32) -> (u64, u32) {
+        let mut session = Rollback::new(transport, local, settings());
+        let mut rng = StdRng::seed_from_u64(local as u64);
+        for _ in 0..frames {
+            session.advance(random_actions(&mut rng)).unwrap();
+            thread::sleep(FRAME);
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !session.is_confirmed() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+            session.poll().unwrap();
+        }
+        assert!(session.is_confirmed());
+        (session.battle.checksum(), session.rollbacks)
+    }
+
+    #[test]
+    fn sessions_agree_under_jitter() {
+        let (first, second) =
+            LatencyChannel::pair(Duration::from_millis(60), Duration::from_millis(40), 7);
+        let first = thread::spawn(move || play(first, 0, 180));
+        let second = thread::spawn(move || play(second, 1, 180));
+        let (first, second) = (first.join().unwrap(), second.join().unwrap());
+        assert_eq!(first.0, second.0);
+        assert!(first.1 + second.1 > 0, "the latency should cause rollbacks");
+    }
+
+    #[test]
+    fn latency_test_matches_the_reference_battle() {
+        latency_test(
+            Duration::from_millis(100),
+            Duration::from_millis(60),
+            settings(),
+            120,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn inputs_far_ahead_are_rejected() {
+        let (mut remote, local) = LatencyChannel::pair(Duration::new(0, 0), Duration::new(0, 0), 1);
+        let mut session = Rollback::new(local, 0, settings());
+        let inputs = Message::Inputs {
+            frame: u32::MAX,
+            actions: Vec::new(),
+        };
+        remote.send(&inputs).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        let error = session.poll().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(session.remote_inputs.len() < 10);
+    }
+}
@@ -0,0 +1,302 @@
+use std::time::{Duration, Instant};
+
+use crate::attack::AttackTable;
+use crate::bot::{self, Weights};
+use crate::game::{self, Action, Game, LineClear};
+use crate::movegen::{self, Placement};
+use crate::tetromino::{self, Tetromino};
+
+/// Boards kept at each step of the search.
+pub const BEAM_WIDTH: usize = 16;
+/// Boards the search may expand for one piece, enough for the default width and depth.
+pub const EXPANSION_BUDGET: usize = 100;
+
+/// Settings of the beam search, and how much it values what the board evaluation of the
+/// greedy bot ignores.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Search {
+    pub width: usize,
+    /// Maximum number of pieces placed ahead, holding included.
+    pub depth: usize,
+    /// The search returns the best placement found so far once it expanded this many boards,
+    /// so that it plays the same moves on any machine.
+    pub expansions: usize,
+    /// Time the search may also be limited to, which makes its moves depend on the machine.
+    pub time_budget: Option<Duration>,
+    /// Value of each garbage line sent.
+    pub attack: f64,
+    /// Value of each line a T-spin could clear on the board right now.
+    pub t_slot: f64,
+    /// Value of keeping a back-to-back chain going.
+    pub back_to_back: f64,
+}
+
+impl Default for Search {
+    fn default() -> Search {
+        Search {
+            width: BEAM_WIDTH,
+            depth: game::PREVIEW_SIZE + 1,
+            expansions: EXPANSION_BUDGET,
+            time_budget: None,
+            attack: 12.0,
+            t_slot: 6.0,
+            back_to_back: 8.0,
+        }
+    }
+}
+
+/// A board reached after a few placements.
+#[derive(Clone)]
+struct Node {
+    board: Vec<Vec<u8>>,
+    hold: Option<u8>,
+    /// Index of the next piece to place in the known pieces.
+    next: usize,
+    can_hold: bool,
+    combo: u32,
+    back_to_back: bool,
+    /// Value of the line clears so far.
+    reward: f64,
+    /// `reward` plus the evaluation of the board.
+    value: f64,
+    /// Index of the first placement that led here.
+    root: usize,
+}
+
+/// Everything the expansion of a node needs.
+struct Context<'a> {
+    /// Current piece then the queue, as piece kinds.
+    pieces: Vec<u8>,
+    weights: &'a Weights,
+    search: &'a Search,
+    table: AttackTable,
+    t_piece: Tetromino,
+}
+
+/// The first placement of the best sequence of placements found for the current piece and the
+/// queue of `game`, using the hold when it helps. The actions of the placement start with a
+/// hold in that case.
+pub fn best_placement(game: &Game, weights: &Weights, search: &Search) -> Option<Placement> {
+    let deadline = search.time_budget.map(|budget| Instant::now() + budget);
+    let current = game.current_piece.as_ref()?;
+    let mut pieces = vec![current.kind()];
+    pieces.extend(game.queue.iter().map(Tetromino::kind));
+    let context = Context {
+        pieces,
+        weights,
+        search,
+        table: AttackTable::default(),
+        t_piece: tetromino::from_kind(7).expect("T pieces exist"),
+    };
+    let start = Node {
+        board: game.game_map.clone(),
+        hold: game.hold.as_ref().map(Tetromino::kind),
+        next: 0,
+        can_hold: game.can_hold,
+        combo: game.combo,
+        back_to_back: game.back_to_back,
+        reward: 0.0,
+        value: 0.0,
+        root: 0,
+    };
+
+    let mut roots = Vec::new();
+    let mut beam = Vec::new();
+    expand(&start, Some(current), &context, &mut beam, Some(&mut roots));
+    let mut best = prune(&mut beam, search.width)?;
+    let mut expanded = 1;
+    for _ in 1..search.depth {
+        let mut children = Vec::new();
+        for node in beam.iter() {
+            let late = deadline.is_some_and(|deadline| Instant::now() > deadline);
+            if expanded >= search.expansions || late {
+                return Some(roots.swap_remove(best));
+            }
+            expanded += 1;
+            if node.next < context.pieces.len() {
+                expand(node, None, &context, &mut children, None);
+            } else {
+                // Nothing left to place, the board is as good as it gets.
+                children.push(node.clone());
+            }
+        }
+        best = match prune(&mut children, search.width) {
+            Some(best) => best,
+            None => break,
+        };
+        beam = children;
+    }
+    Some(roots.swap_remove(best))
+}
+
+/// Keeps the `width` best nodes. Returns the root of the best one.
+fn prune(nodes: &mut Vec<Node>, width: usize) -> Option<usize> {
+    nodes.sort_by(|a, b| {
+        b.value
+            .partial_cmp(&a.value)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    nodes.truncate(width);
+    nodes.first().map(|node| node.root)
+}
+
+/// Adds every node reachable from `node` with one placement to `children`, holding or not.
+/// `current` is the actual current piece at the root, where `roots` collects the placements.
+fn expand(
+    node: &Node,
+    current: Option<&Tetromino>,
+    context: &Context,
+    children: &mut Vec<Node>,
+    mut roots: Option<&mut Vec<Placement>>,
+) {
+    let pieces = &context.pieces;
+    let next = pieces[node.next];
+    let mut choices = Vec::new();
+    let piece = match current {
+        Some(piece) => piece.clone(),
+        None => spawn(next),
+    };
+    choices.push((piece, node.hold, node.next + 1, false));
+    if node.can_hold {
+        match node.hold {
+            Some(held) if held != next => {
+                choices.push((spawn(held), Some(next), node.next + 1, true))
+            }
+            None if node.next + 1 < pieces.len() => choices.push((
+                spawn(pieces[node.next + 1]),
+                Some(next),
+                node.next + 2,
+                true,
+            )),
+            _ => {}
+        }
+    }
+
+    for (piece, hold, next, held) in choices {
+        for mut placement in movegen::placements(&node.board, &piece) {
+            let mut child = place(node, &piece, &placement, context);
+            child.hold = hold;
+            child.next = next;
+            if let Some(ref mut roots) = roots {
+                if held {
+                    placement.actions.insert(0, Action::Hold);
+                }
+                child.root = roots.len();
+                roots.push(placement);
+            }
+            children.push(child);
+        }
+    }
+}
+
+fn spawn(kind: u8) -> Tetromino {
+    tetromino::from_kind(kind).expect("Piece kinds go from 1 to 7")
+}
+
+/// Locks `piece` as in `placement`, clearing lines the way `Game::make_permanent` does.
+fn place(node: &Node, piece: &Tetromino, placement: &Placement, context: &Context) -> Node {
+    let search = context.search;
+    let mut board = bot::lock_piece(
+        &node.board,
+        piece,
+        placement.state as usize,
+        placement.x,
+        placement.y,
+    );
+    let lines = board.iter().filter(|row| !row.contains(&0)).count() as u32;
+    let features = bot::measure(&mut board, piece, placement);
+
+    let mut clear = LineClear {
+        lines,
+        t_spin: placement.t_spin,
+        ..LineClear::default()
+    };
+    let mut combo = 0;
+    let mut back_to_back = node.back_to_back;
+    let mut reward = node.reward;
+    if lines > 0 {
+        clear.all_clear = board.iter().all(|row| row.iter().all(|case| *case == 0));
+        clear.combo = node.combo;
+        clear.back_to_back = node.back_to_back && clear.is_difficult();
+        combo = node.combo + 1;
+        back_to_back = clear.is_difficult();
+        reward += search.attack * context.table.attack(&clear) as f64;
+        if node.back_to_back && !back_to_back {
+            reward -= search.back_to_back;
+        }
+    }
+
+    let mut value = reward
+        + context.weights.score(&features)
+        + search.t_slot * t_slot(&board, &context.t_piece) as f64;
+    if back_to_back {
+        value += search.back_to_back;
+    }
+    Node {
+        board,
+        hold: node.hold,
+        next: node.next,
+        can_hold: true,
+        combo,
+        back_to_back,
+        reward,
+        value,
+        root: node.root,
+    }
+}
+
+/// Lines cleared by the best T-spin ready on `board`, if any: a T dropped straight down and
+/// rotated once where it rests, rotations happening in place in this game.
+fn t_slot(board: &[Vec<u8>], t_piece: &Tetromino) -> u32 {
+    // Pieces fall freely down to the top of the stack.
+    let top = board
+        .iter()
+        .position(|row| row.iter().any(|case| *case != 0))
+        .unwrap_or(board.len());
+    let start = top.saturating_sub(4);
+    let mut t = t_piece.clone();
+    let states = t.states.len();
+    let mut best = 0;
+    for state in 0..states {
+        let previous = (state + states - 1) % states;
+        for x in -3..board[0].len() as isize {
+            if !t.test_position(board, previous, x, start) {
+                continue;
+            }
+            let mut y = start;
+            while t.test_position(board, previous, x, y + 1) {
+                y += 1;
+            }
+            if !t.test_position(board, state, x, y) || t.test_position(board, state, x, y + 1) {
+                continue;
+            }
+            t.current_state = state as u8;
+            t.x = x;
+            t.y = y;
+            if game::t_spin_corners(board, &t) {
+                best = best.max(complete_rows(board, &t));
+            }
+        }
+    }
+    best
+}
+
+/// Rows that `piece` would complete where it is.
+fn complete_rows(board: &[Vec<u8>], piece: &Tetromino) -> u32 {
+    let state = &piece.states[piece.current_state as usize];
+    let mut rows = 0;
+    for (line_num, line) in state.iter().enumerate() {
+        let y = piece.y + line_num;
+        if y >= board.len() || line.iter().all(|case| *case == 0) {
+            continue;
+        }
+        let complete = board[y].iter().enumerate().all(|(x, case)| {
+            let decal = x as isize - piece.x;
+            *case != 0 || (0..4).contains(&decal) && line[decal as usize] != 0
+        });
+        if complete {
+            rows += 1;
+        }
+    }
+    rows
+}
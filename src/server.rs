@@ -0,0 +1,339 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::attack::GARBAGE_DELAY;
+use crate::battle::{Battle, Outcome};
+use crate::game::Action;
+use crate::net::{self, invalid_data, Connection, Lockstep, Message, Session, Settings};
+
+/// Settings of every match played on a server.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub garbage_delay: u32,
+    pub input_delay: u32,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            garbage_delay: GARBAGE_DELAY,
+            input_delay: net::INPUT_DELAY,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MatchResult {
+    Finished(Outcome),
+    /// The given player left before the end.
+    Forfeit(usize),
+}
+
+/// What the server tells about a match once it is over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    pub room: String,
+    pub seed: u64,
+    pub frames: u32,
+    pub result: MatchResult,
+}
+
+/// How long the client of `self_test` that leaves waits in its room, and how long the server
+/// gets to notice it left.
+const LEAVER_WAIT: Duration = Duration::from_millis(50);
+
+/// Clients waiting for an opponent, by room name.
+type Rooms = Mutex<HashMap<String, Connection>>;
+
+/// Accepts clients on `listener` forever. Every client first joins a room; the second one
+/// to join starts a match between them, after which the server relays the inputs of each
+/// player to the other. Garbage is not sent over the network since both clients compute it
+/// from the inputs, and so does the server to find out the result, which it sends on
+/// `reports`.
+pub fn serve(listener: TcpListener, config: ServerConfig, reports: Sender<Report>) {
+    let rooms = Arc::new(Rooms::default());
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Connection failed: {}", e);
+                continue;
+            }
+        };
+        let rooms = rooms.clone();
+        let reports = reports.clone();
+        thread::spawn(move || {
+            if let Err(e) = lobby(stream, &rooms, config, reports) {
+                eprintln!("Client dropped: {}", e);
+            }
+        });
+    }
+}
+
+/// Puts a new client in the room it asks for, and plays the match if the room is full. A
+/// client that left while waiting gives its place to the new one.
+fn lobby(
+    stream: TcpStream,
+    rooms: &Rooms,
+    config: ServerConfig,
+    reports: Sender<Report>,
+) -> io::Result<()> {
+    let mut connection = Connection::new(stream)?;
+    let room = match connection.receive()? {
+        Message::Join { room } => room,
+        message => return Err(invalid_data(format!("expected join, got {:?}", message))),
+    };
+    let opponent = {
+        let mut rooms = rooms.lock().expect("Room list poisoned");
+        match rooms.remove(&room) {
+            Some(opponent) if opponent.is_open() => opponent,
+            _ => {
+                rooms.insert(room, connection);
+                return Ok(());
+            }
+        }
+    };
+    let report = run_match(room, opponent, connection, config)?;
+    let _ = reports.send(report);
+    Ok(())
+}
+
+/// Starts a match between the two players and relays their messages until one of them
+/// leaves, or until both left once the match is over.
+fn run_match(
+    room: String,
+    first: Connection,
+    second: Connection,
+    config: ServerConfig,
+) -> io::Result<Report> {
+    let seed = rand::random();
+    let settings = Settings {
+        seeds: [seed, seed.wrapping_add(1)],
+        garbage_delay: config.garbage_delay,
+        input_delay: config.input_delay,
+    };
+    let (sender, messages) = mpsc::channel();
+    let mut writers = Vec::new();
+    for (player, connection) in vec![first, second].into_iter().enumerate() {
+        let (mut reader, mut writer) = connection.into_parts();
+        Message::Start {
+            player: player as u8,
+            settings,
+        }
+        .write_to(&mut writer)?;
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            let message = Message::read_from(&mut reader);
+            let failed = message.is_err();
+            if sender.send((player, message)).is_err() || failed {
+                break;
+            }
+        });
+        writers.push(writer);
+    }
+
+    let mut referee = Referee::new(settings);
+    let mut outcome = None;
+    let mut leaver = 0;
+    let mut left = [false; 2];
+    for (player, message) in messages {
+        let (frame, actions) = match message {
+            Ok(Message::Inputs { frame, actions }) => (frame, actions),
+            // Players leave as soon as they see the end of the match, the other one possibly
+            // still playing the last frames with the inputs relayed to it.
+            _ if outcome.is_some() => {
+                left[player] = true;
+                if left.iter().all(|left| *left) {
+                    break;
+                }
+                continue;
+            }
+            _ => {
+                leaver = player;
+                break;
+            }
+        };
+        let _ = Message::Inputs {
+            frame,
+            actions: actions.clone(),
+        }
+        .write_to(&mut writers[1 - player]);
+        if outcome.is_none() {
+            match referee.receive(player, frame, actions) {
+                Ok(result) => outcome = result,
+                Err(_) => {
+                    leaver = player;
+                    break;
+                }
+            }
+        }
+    }
+    let _ = Message::Quit.write_to(&mut writers[1 - leaver]);
+    for writer in writers.iter() {
+        let _ = writer.shutdown(Shutdown::Both);
+    }
+    Ok(Report {
+        room,
+        seed,
+        frames: referee.battle.frame,
+        result: match outcome {
+            Some(outcome) => MatchResult::Finished(outcome),
+            None => MatchResult::Forfeit(leaver),
+        },
+    })
+}
+
+/// Simulates a match from the relayed inputs, like the clients do.
+struct Referee {
+    battle: Battle,
+    input_delay: u32,
+    inputs: [VecDeque<Vec<Action>>; 2],
+    received: [u32; 2],
+}
+
+impl Referee {
+    fn new(settings: Settings) -> Referee {
+        Referee {
+            battle: Battle::new(settings.seeds, settings.garbage_delay),
+            input_delay: settings.input_delay,
+            inputs: [VecDeque::new(), VecDeque::new()],
+            received: [0, 0],
+        }
+    }
+
+    /// Records the inputs of `player` for `frame` and simulates every frame both players sent
+    /// their inputs for. Returns the outcome once the match is over.
+    fn receive(
+        &mut self,
+        player: usize,
+        frame: u32,
+        actions: Vec<Action>,
+    ) -> io::Result<Option<Outcome>> {
+        let expected = self.input_delay + self.received[player];
+        if frame != expected {
+            return Err(invalid_data(format!(
+                "expected inputs for frame {}, got {}",
+                expected, frame
+            )));
+        }
+        self.received[player] += 1;
+        self.inputs[player].push_back(actions);
+
+        while self.battle.outcome().is_none() {
+            if self.battle.frame < self.input_delay {
+                self.battle.step([&[], &[]]);
+            } else if self.inputs.iter().all(|inputs| !inputs.is_empty()) {
+                let first = self.inputs[0].pop_front().unwrap_or_default();
+                let second = self.inputs[1].pop_front().unwrap_or_default();
+                self.battle.step([&first, &second]);
+            } else {
+                break;
+            }
+        }
+        Ok(self.battle.outcome())
+    }
+}
+
+/// Joins `room` on the server at `addr` and plays a match without a screen, `script` choosing
+/// the actions of every frame from the battle and the index of the local player. Returns the
+/// outcome, or `None` if the opponent left first.
+pub fn play_scripted<F>(addr: &str, room: &str, mut script: F) -> io::Result<Option<Outcome>>
+where
+    F: FnMut(&Battle, usize) -> Vec<Action>,
+{
+    let (connection, player, settings) = Connection::join(addr, room)?;
+    let mut session = Lockstep::new(connection, player, settings);
+    loop {
+        let actions = script(session.battle(), player);
+        if !session.advance(actions)? {
+            return Ok(None);
+        }
+        if let Some(outcome) = session.outcome() {
+            session.quit();
+            return Ok(Some(outcome));
+        }
+    }
+}
+
+/// Starts a server on a free local port and has two scripted clients pressing random keys
+/// play a match in it, after a first client left the room while waiting. Checks that both
+/// clients and the server agree on the result and returns the report of the server.
+pub fn self_test(config: ServerConfig, seed: u64) -> io::Result<Report> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?.to_string();
+    let (sender, reports) = mpsc::channel();
+    thread::spawn(move || serve(listener, config, sender));
+
+    let mut leaver = Connection::new(TcpStream::connect(&addr)?)?;
+    leaver.send(&Message::Join {
+        room: "self-test".to_owned(),
+    })?;
+    // Gives the server the time to put it in the room before it leaves.
+    thread::sleep(LEAVER_WAIT);
+    drop(leaver);
+    thread::sleep(LEAVER_WAIT);
+
+    let clients: Vec<_> = (0..2)
+        .map(|client| {
+            let addr = addr.clone();
+            thread::spawn(move || {
+                let mut rng = StdRng::seed_from_u64(seed.wrapping_add(client));
+                play_scripted(&addr, "self-test", |_, _| random_actions(&mut rng))
+            })
+        })
+        .collect();
+    let mut outcomes = Vec::new();
+    for client in clients {
+        let outcome = client
+            .join()
+            .map_err(|_| io::Error::other("client panicked"))??;
+        outcomes.push(outcome);
+    }
+    let report = reports
+        .recv()
+        .map_err(|_| io::Error::other("server stopped"))?;
+    for outcome in outcomes {
+        if outcome.map(MatchResult::Finished) != Some(report.result) {
+            return Err(invalid_data(format!(
+                "client saw {:?}, server reported {:?}",
+                outcome, report.result
+            )));
+        }
+    }
+    Ok(report)
+}
+
+fn random_actions(rng: &mut StdRng) -> Vec<Action> {
+    const ACTIONS: [Action; 5] = [
+        Action::Left,
+        Action::Right,
+        Action::Rotate,
+        Action::SoftDrop,
+        Action::HardDrop,
+    ];
+    if rng.gen_bool(0.2) {
+        vec![ACTIONS[rng.gen_range(0, ACTIONS.len())]]
+    } else {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted_clients_agree_with_the_server() {
+        let report = self_test(ServerConfig::default(), 3).unwrap();
+        assert_eq!(report.room, "self-test");
+        assert!(matches!(report.result, MatchResult::Finished(_)));
+        assert!(report.frames > 0);
+    }
+}
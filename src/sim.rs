@@ -0,0 +1,293 @@
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::bot::{Bot, BotSpec};
+use crate::game::{self, Action, Game, LineClear};
+use crate::mode::{Mode, ModeState};
+
+/// Frames per second of simulated games, as in the game.
+pub const FPS: u32 = 60;
+
+/// How a simulated game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ending {
+    ToppedOut,
+    /// The goal of the mode was reached.
+    Completed,
+    /// The time limit of the mode ran out.
+    TimeUp,
+    /// The simulation gave up on a game still going.
+    FrameLimit,
+}
+
+impl Ending {
+    /// The name of the ending in reports.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Ending::ToppedOut => "topped_out",
+            Ending::Completed => "completed",
+            Ending::TimeUp => "time_up",
+            Ending::FrameLimit => "frame_limit",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GameReport {
+    pub seed: u64,
+    pub lines: u32,
+    pub score: u32,
+    pub pieces: u32,
+    pub frames: u32,
+    pub ending: Ending,
+}
+
+impl GameReport {
+    /// Time played, in seconds.
+    pub fn seconds(&self) -> f64 {
+        self.frames as f64 / FPS as f64
+    }
+}
+
+/// A game played frame by frame without a window, the way the game loop plays it.
+pub struct Simulation {
+    pub game: Game,
+    pub mode: Mode,
+    state: ModeState,
+    pub frames: u32,
+    /// Pieces spawned so far.
+    pub pieces: u32,
+    /// Set once the game is over, after which frames do nothing.
+    pub ending: Option<Ending>,
+    gravity: u32,
+}
+
+impl Simulation {
+    /// A new game of `mode`, its first piece spawned.
+    pub fn new(mode: Mode, seed: u64) -> Simulation {
+        let mut game = Game::with_seed(seed);
+        mode.setup(&mut game);
+        let mut simulation = Simulation {
+            game,
+            mode,
+            state: ModeState::new(&mode),
+            frames: 0,
+            pieces: 0,
+            ending: None,
+            gravity: 0,
+        };
+        simulation.spawn();
+        simulation
+    }
+
+    /// Time played.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis(self.frames as u64 * 1000 / FPS as u64)
+    }
+
+    /// Plays `actions` then one frame of gravity, and spawns the next piece if needed. Returns
+    /// what the locks of the frame did.
+    pub fn frame(&mut self, actions: &[Action]) -> Vec<LineClear> {
+        let mut clears = Vec::new();
+        if self.ending.is_some() {
+            return clears;
+        }
+        for action in actions.iter() {
+            if *action == Action::SoftDrop {
+                self.gravity = 0;
+            }
+            if let Some(clear) = self.game.apply_action(*action) {
+                self.gravity = 0;
+                clears.push(clear);
+            }
+        }
+        self.gravity += 1;
+        let mode = self.mode;
+        if mode.has_gravity() && self.gravity >= game::level_frames(self.game.current_level) {
+            self.gravity = 0;
+            clears.extend(self.game.apply_action(Action::SoftDrop));
+        }
+        self.frames += 1;
+
+        let elapsed = self.elapsed();
+        if !mode.update_elapsed(&mut self.game, &mut self.state, elapsed) {
+            self.ending = Some(Ending::ToppedOut);
+        } else if mode.is_over_elapsed(elapsed) {
+            self.ending = Some(Ending::TimeUp);
+        } else if mode.is_complete(&self.game) {
+            self.ending = Some(Ending::Completed);
+        } else {
+            self.spawn();
+        }
+        clears
+    }
+
+    /// Spawns the next piece if the last one got locked.
+    fn spawn(&mut self) {
+        // Zen mode snapshots are only needed for undo, which nothing does here.
+        if self.game.current_piece.is_some() {
+            return;
+        }
+        if !self.mode.before_spawn(&mut self.game) {
+            self.ending = Some(Ending::ToppedOut);
+            return;
+        }
+        let piece = self.game.create_new_tetromino();
+        if !piece.test_current_position(&self.game.game_map) {
+            if self.mode.can_top_out() {
+                self.ending = Some(Ending::ToppedOut);
+                return;
+            }
+            self.game.clear_board();
+        }
+        self.game.current_piece = Some(piece);
+        self.pieces += 1;
+    }
+}
+
+/// Plays a game of `mode` with `bot`, as fast as possible, until it ends or `max_frames` frames
+/// have passed.
+pub fn play(bot: &mut Bot, mode: Mode, seed: u64, max_frames: u32) -> GameReport {
+    let mut simulation = Simulation::new(mode, seed);
+    let ending = loop {
+        if let Some(ending) = simulation.ending {
+            break ending;
+        }
+        if simulation.frames >= max_frames {
+            break Ending::FrameLimit;
+        }
+        let actions = bot.next_actions(&simulation.game);
+        simulation.frame(&actions);
+    };
+    GameReport {
+        seed,
+        lines: simulation.game.num_lines,
+        score: simulation.game.score,
+        pieces: simulation.pieces,
+        frames: simulation.frames,
+        ending,
+    }
+}
+
+/// Plays a game for every seed of `seeds`, each with a new bot built from `spec`, on `jobs`
+/// threads at once. Returns the reports in seed order.
+pub fn run(
+    spec: &BotSpec,
+    delay: u32,
+    mode: Mode,
+    seeds: Range<u64>,
+    max_frames: u32,
+    jobs: usize,
+) -> io::Result<Vec<GameReport>> {
+    let count = seeds.end.saturating_sub(seeds.start) as usize;
+    parallel(count, jobs, |index| {
+        let mut bot = spec.build(delay)?;
+        Ok(play(&mut bot, mode, seeds.start + index as u64, max_frames))
+    })
+}
+
+/// Runs `task` for every index below `count`, on `jobs` threads at once. Returns the results in
+/// index order, or the first error.
+pub fn parallel<T, F>(count: usize, jobs: usize, task: F) -> io::Result<Vec<T>>
+where
+    T: Send,
+    F: Fn(usize) -> io::Result<T> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(count));
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1))
+            .map(|_| {
+                scope.spawn(|| -> io::Result<()> {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::SeqCst);
+                        if index >= count {
+                            return Ok(());
+                        }
+                        let result = task(index)?;
+                        results
+                            .lock()
+                            .expect("Results poisoned")
+                            .push((index, result));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().expect("Simulation thread panicked"))
+    })?;
+    let mut results = results.into_inner().expect("Results poisoned");
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Distribution of a value over several games.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub min: f64,
+    /// Tenth percentile.
+    pub p10: f64,
+    pub median: f64,
+    /// Ninetieth percentile.
+    pub p90: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// `None` without any value.
+    pub fn of(values: &[f64]) -> Option<Stats> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+        Some(Stats {
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            min: sorted[0],
+            p10: percentile(10),
+            median: percentile(50),
+            p90: percentile(90),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Summary {
+    pub games: usize,
+    pub topped_out: usize,
+    pub lines: Option<Stats>,
+    pub score: Option<Stats>,
+    pub pieces: Option<Stats>,
+    /// Seconds played by the games that topped out.
+    pub time_to_top_out: Option<Stats>,
+}
+
+pub fn summarize(reports: &[GameReport]) -> Summary {
+    let stats = |value: &dyn Fn(&GameReport) -> f64| {
+        Stats::of(&reports.iter().map(value).collect::<Vec<_>>())
+    };
+    let topped_out: Vec<f64> = reports
+        .iter()
+        .filter(|report| report.ending == Ending::ToppedOut)
+        .map(GameReport::seconds)
+        .collect();
+    Summary {
+        games: reports.len(),
+        topped_out: topped_out.len(),
+        lines: stats(&|report| report.lines as f64),
+        score: stats(&|report| report.score as f64),
+        pieces: stats(&|report| report.pieces as f64),
+        time_to_top_out: Stats::of(&topped_out),
+    }
+}
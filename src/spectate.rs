@@ -0,0 +1,197 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::game::{Game, GARBAGE};
+use crate::net::{invalid_data, read_u32, read_u8};
+use crate::tetromino::{self, Tetromino};
+
+/// Spectators that take longer than this to receive a frame get dropped, so they never slow
+/// the game down.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// One board as shown to spectators: the game and the text lines displayed with it.
+#[derive(Clone)]
+pub struct Board {
+    pub game: Game,
+    pub stats: Vec<String>,
+}
+
+/// Sends the boards of a running game to every spectator connected to it.
+pub struct Broadcaster {
+    spectators: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Broadcaster {
+    /// Accepts spectators on `addr` in the background.
+    pub fn bind(addr: &str) -> io::Result<Broadcaster> {
+        let listener = TcpListener::bind(addr)?;
+        let spectators = Arc::new(Mutex::new(Vec::new()));
+        let accepted = spectators.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                if stream.set_nodelay(true).is_ok()
+                    && stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+                {
+                    accepted.lock().expect("Spectators poisoned").push(stream);
+                }
+            }
+        });
+        Ok(Broadcaster { spectators })
+    }
+
+    /// Sends the current state of `boards` to the spectators, forgetting the ones that left.
+    pub fn publish(&mut self, boards: &[(&Game, &[String])]) {
+        let mut spectators = self.spectators.lock().expect("Spectators poisoned");
+        if spectators.is_empty() {
+            return;
+        }
+        let mut bytes = Vec::new();
+        bytes.push(boards.len() as u8);
+        for (game, stats) in boards {
+            write_board(game, stats, &mut bytes);
+        }
+        spectators.retain(|stream| {
+            let mut writer: &TcpStream = stream;
+            writer.write_all(&bytes).is_ok()
+        });
+    }
+}
+
+/// Receives the boards published by a game in the background.
+pub struct Spectator {
+    frames: Receiver<io::Result<Vec<Board>>>,
+    latest: Vec<Board>,
+}
+
+impl Spectator {
+    pub fn connect(addr: &str) -> io::Result<Spectator> {
+        let mut reader = BufReader::new(TcpStream::connect(addr)?);
+        let (sender, frames) = mpsc::channel();
+        thread::spawn(move || loop {
+            let boards = read_boards(&mut reader);
+            let failed = boards.is_err();
+            if sender.send(boards).is_err() || failed {
+                break;
+            }
+        });
+        Ok(Spectator {
+            frames,
+            latest: Vec::new(),
+        })
+    }
+
+    /// Returns the most recent boards, which are empty until the first frame arrives. Fails
+    /// once the game is gone.
+    pub fn latest(&mut self) -> io::Result<&[Board]> {
+        loop {
+            match self.frames.try_recv() {
+                Ok(boards) => self.latest = boards?,
+                Err(TryRecvError::Empty) => return Ok(&self.latest),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "connection closed",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+fn write_board(game: &Game, stats: &[String], bytes: &mut Vec<u8>) {
+    bytes.push(game.game_map.len() as u8);
+    bytes.push(game.game_map[0].len() as u8);
+    for row in game.game_map.iter() {
+        bytes.extend_from_slice(row);
+    }
+    match game.current_piece {
+        Some(ref piece) => {
+            bytes.push(piece.kind());
+            bytes.extend_from_slice(&(piece.x as i32).to_be_bytes());
+            bytes.push(piece.y as u8);
+            bytes.push(piece.current_state);
+        }
+        None => bytes.push(0),
+    }
+    bytes.push(game.hold.as_ref().map_or(0, Tetromino::kind));
+    bytes.push(game.queue.len() as u8);
+    bytes.extend(game.queue.iter().map(Tetromino::kind));
+    bytes.extend_from_slice(&game.score.to_be_bytes());
+    bytes.extend_from_slice(&game.num_lines.to_be_bytes());
+    bytes.extend_from_slice(&game.current_level.to_be_bytes());
+    bytes.push(stats.len() as u8);
+    for line in stats {
+        let text = &line.as_bytes()[..line.len().min(u8::MAX as usize)];
+        bytes.push(text.len() as u8);
+        bytes.extend_from_slice(text);
+    }
+}
+
+fn read_boards<R: Read>(reader: &mut R) -> io::Result<Vec<Board>> {
+    let count = read_u8(reader)?;
+    (0..count).map(|_| read_board(reader)).collect()
+}
+
+/// Rebuilds a game holding everything spectators get to see.
+fn read_board<R: Read>(reader: &mut R) -> io::Result<Board> {
+    let mut game = Game::with_seed(0);
+    let rows = read_u8(reader)? as usize;
+    let columns = read_u8(reader)? as usize;
+    if rows == 0 || columns == 0 {
+        return Err(invalid_data("empty board".to_owned()));
+    }
+    game.game_map = vec![vec![0; columns]; rows];
+    for row in game.game_map.iter_mut() {
+        reader.read_exact(row)?;
+        if row.iter().any(|case| *case > GARBAGE) {
+            return Err(invalid_data("unknown case".to_owned()));
+        }
+    }
+    game.current_piece = match read_u8(reader)? {
+        0 => None,
+        kind => {
+            let mut piece = read_piece(kind)?;
+            piece.x = read_u32(reader)? as i32 as isize;
+            piece.y = read_u8(reader)? as usize;
+            piece.current_state = read_u8(reader)?;
+            if piece.current_state as usize >= piece.states.len() {
+                return Err(invalid_data(format!(
+                    "bad piece state {}",
+                    piece.current_state
+                )));
+            }
+            Some(piece)
+        }
+    };
+    game.hold = match read_u8(reader)? {
+        0 => None,
+        kind => Some(read_piece(kind)?),
+    };
+    let queue_size = read_u8(reader)?;
+    game.queue = (0..queue_size)
+        .map(|_| read_piece(read_u8(reader)?))
+        .collect::<io::Result<_>>()?;
+    game.score = read_u32(reader)?;
+    game.num_lines = read_u32(reader)?;
+    game.current_level = read_u32(reader)?;
+
+    let mut stats = Vec::new();
+    for _ in 0..read_u8(reader)? {
+        let mut line = vec![0; read_u8(reader)? as usize];
+        reader.read_exact(&mut line)?;
+        stats.push(String::from_utf8_lossy(&line).into_owned());
+    }
+    Ok(Board { game, stats })
+}
+
+fn read_piece(kind: u8) -> io::Result<Tetromino> {
+    tetromino::from_kind(kind).ok_or_else(|| invalid_data(format!("unknown piece {}", kind)))
+}
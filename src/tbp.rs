@@ -0,0 +1,861 @@
+//! Tetris Bot Protocol: JSON messages, one per line, between a frontend and a bot engine
+//! reading them on its standard input and answering on its standard output.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bot::Bot;
+use crate::game::{Action, Game, GARBAGE};
+use crate::mode::SURVIVAL_HOLES;
+use crate::movegen::{self, Placement};
+use crate::net::invalid_data;
+use crate::tetromino::{self, Tetromino};
+
+/// Rows of a TBP board, most of them above our board.
+pub const BOARD_HEIGHT: usize = 40;
+/// Longest wait for an answer of the bot.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Time a bot gets to exit after being told to quit.
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Piece {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+}
+
+impl Piece {
+    /// See `tetromino::from_kind`. Pieces are matched by shape: the J of this game is the L of
+    /// other games and the other way round.
+    pub fn from_kind(kind: u8) -> Option<Piece> {
+        match kind {
+            1 => Some(Piece::I),
+            2 => Some(Piece::L),
+            3 => Some(Piece::J),
+            4 => Some(Piece::O),
+            5 => Some(Piece::S),
+            6 => Some(Piece::Z),
+            7 => Some(Piece::T),
+            _ => None,
+        }
+    }
+
+    pub fn kind(self) -> u8 {
+        match self {
+            Piece::I => 1,
+            Piece::L => 2,
+            Piece::J => 3,
+            Piece::O => 4,
+            Piece::S => 5,
+            Piece::Z => 6,
+            Piece::T => 7,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Piece::I => 'I',
+            Piece::O => 'O',
+            Piece::T => 'T',
+            Piece::L => 'L',
+            Piece::J => 'J',
+            Piece::S => 'S',
+            Piece::Z => 'Z',
+        }
+    }
+
+    /// Cases of the piece around its center when pointing north, y going up.
+    fn offsets(self) -> [(i32, i32); 4] {
+        match self {
+            Piece::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Piece::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            Piece::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Piece::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Piece::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Piece::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Piece::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+const ORIENTATIONS: [Orientation; 4] = [
+    Orientation::North,
+    Orientation::East,
+    Orientation::South,
+    Orientation::West,
+];
+
+impl Orientation {
+    /// Turns an offset of a piece pointing north so that it points this way.
+    fn rotate(self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self {
+            Orientation::North => (x, y),
+            Orientation::East => (y, -x),
+            Orientation::South => (-x, -y),
+            Orientation::West => (-y, x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+/// Where a piece locks: the column and row of its center, the bottom row being 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub piece: Piece,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: Location,
+    pub spin: Spin,
+}
+
+/// The state of a game as the bot sees it. The queue starts with the current piece, and the
+/// board is `BOARD_HEIGHT` rows of 10 cases from the bottom up, holding piece letters, `G` for
+/// garbage or nothing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Start {
+    pub hold: Option<Piece>,
+    pub queue: Vec<Piece>,
+    pub combo: u32,
+    pub back_to_back: bool,
+    pub board: Vec<Vec<Option<char>>>,
+}
+
+/// Messages sent to the bot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules {},
+    Start(Start),
+    Stop {},
+    Suggest {},
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: Piece,
+    },
+    Quit {},
+}
+
+/// Messages sent by the bot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    Ready {},
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+}
+
+/// The state of `game` for a `start` message, if it has a current piece.
+pub fn start(game: &Game) -> Option<Start> {
+    let current = game.current_piece.as_ref()?;
+    let mut queue = vec![Piece::from_kind(current.kind())?];
+    for piece in game.queue.iter() {
+        queue.push(Piece::from_kind(piece.kind())?);
+    }
+    let columns = game.game_map[0].len();
+    let mut board: Vec<Vec<Option<char>>> = game
+        .game_map
+        .iter()
+        .rev()
+        .map(|row| row.iter().map(|case| letter(*case)).collect())
+        .collect();
+    board.resize(BOARD_HEIGHT.max(board.len()), vec![None; columns]);
+    Some(Start {
+        hold: game
+            .hold
+            .as_ref()
+            .and_then(|piece| Piece::from_kind(piece.kind())),
+        queue,
+        combo: game.combo,
+        back_to_back: game.back_to_back,
+        board,
+    })
+}
+
+pub(crate) fn letter(case: u8) -> Option<char> {
+    match case {
+        0 => None,
+        GARBAGE => Some('G'),
+        kind => Piece::from_kind(kind).map(Piece::letter),
+    }
+}
+
+fn case(letter: Option<char>) -> u8 {
+    match letter {
+        None => 0,
+        Some(letter) => [
+            Piece::I,
+            Piece::J,
+            Piece::L,
+            Piece::O,
+            Piece::S,
+            Piece::Z,
+            Piece::T,
+        ]
+        .iter()
+        .find(|piece| piece.letter() == letter)
+        .map_or(GARBAGE, |piece| piece.kind()),
+    }
+}
+
+/// A game in the state described by `start`, on a board of `rows` rows. Cases above the board
+/// are lost.
+pub fn game(start: &Start, rows: usize) -> Option<Game> {
+    let mut game = Game::with_seed(0);
+    let columns = start.board.first().map_or(0, Vec::len);
+    if columns == 0 {
+        return None;
+    }
+    game.game_map = (0..rows)
+        .rev()
+        .map(|y| match start.board.get(y) {
+            Some(row) => row.iter().map(|letter| case(*letter)).collect(),
+            None => vec![0; columns],
+        })
+        .collect();
+    let (current, queue) = start.queue.split_first()?;
+    game.current_piece = tetromino::from_kind(current.kind());
+    game.queue = queue
+        .iter()
+        .filter_map(|piece| tetromino::from_kind(piece.kind()))
+        .collect();
+    game.hold = start
+        .hold
+        .and_then(|piece| tetromino::from_kind(piece.kind()));
+    game.combo = start.combo;
+    game.back_to_back = start.back_to_back;
+    Some(game)
+}
+
+/// Cases covered by the piece of `location`, as (x, y) with y going up.
+pub(crate) fn location_cases(location: &Location) -> Vec<(i32, i32)> {
+    let mut cases: Vec<_> = location
+        .piece
+        .offsets()
+        .iter()
+        .map(|offset| {
+            let (x, y) = location.orientation.rotate(*offset);
+            (location.x + x, location.y + y)
+        })
+        .collect();
+    cases.sort_unstable();
+    cases
+}
+
+/// Cases covered by `piece` in `state` at (`x`, `y`), as (x, y) with y going up from the
+/// bottom of a board of `rows` rows.
+pub(crate) fn placement_cases(
+    piece: &Tetromino,
+    state: u8,
+    x: isize,
+    y: usize,
+    rows: usize,
+) -> Vec<(i32, i32)> {
+    let mut cases = Vec::new();
+    for (line_num, line) in piece.states[state as usize].iter().enumerate() {
+        for (case_num, case) in line.iter().enumerate() {
+            if *case != 0 {
+                let row = (y + line_num) as i32;
+                cases.push(((x + case_num as isize) as i32, rows as i32 - 1 - row));
+            }
+        }
+    }
+    cases.sort_unstable();
+    cases
+}
+
+/// The move locking `piece` as in `placement` on a board of `rows` rows.
+pub fn to_move(piece: &Tetromino, placement: &Placement, rows: usize) -> Option<Move> {
+    let kind = Piece::from_kind(piece.kind())?;
+    let cases = placement_cases(piece, placement.state, placement.x, placement.y, rows);
+    let spin = if placement.t_spin {
+        Spin::Full
+    } else {
+        Spin::None
+    };
+    let location = locate(kind, &cases)?;
+    Some(Move { location, spin })
+}
+
+/// The location of `piece` covering `cases`, sorted as `location_cases` sorts them.
+pub(crate) fn locate(piece: Piece, cases: &[(i32, i32)]) -> Option<Location> {
+    let first = *cases.first()?;
+    for orientation in ORIENTATIONS.iter() {
+        // The first case can be any case of the piece.
+        for offset in piece.offsets().iter() {
+            let (x, y) = orientation.rotate(*offset);
+            let location = Location {
+                piece,
+                orientation: *orientation,
+                x: first.0 - x,
+                y: first.1 - y,
+            };
+            if location_cases(&location) == cases {
+                return Some(location);
+            }
+        }
+    }
+    None
+}
+
+/// The placement of the current piece of `game`, or of the piece it would get by holding,
+/// that makes `mv`. `None` if no sequence of actions can bring the piece there.
+pub fn find_placement(game: &Game, mv: &Move) -> Option<Placement> {
+    let current = game.current_piece.as_ref()?;
+    let kind = mv.location.piece.kind();
+    let (piece, held) = if current.kind() == kind {
+        (current.clone(), false)
+    } else {
+        let next = match game.hold {
+            Some(ref held) => held.kind(),
+            None => game.queue.front()?.kind(),
+        };
+        if !game.can_hold || next != kind {
+            return None;
+        }
+        (tetromino::from_kind(kind)?, true)
+    };
+    let rows = game.game_map.len();
+    let cases = location_cases(&mv.location);
+    let spin = mv.spin != Spin::None;
+    let mut found = None;
+    for placement in movegen::placements(&game.game_map, &piece) {
+        if placement_cases(&piece, placement.state, placement.x, placement.y, rows) != cases {
+            continue;
+        }
+        let exact = placement.t_spin == spin;
+        if found.is_none() || exact {
+            found = Some(placement);
+        }
+        if exact {
+            break;
+        }
+    }
+    let mut placement = found?;
+    if held {
+        placement.actions.insert(0, Action::Hold);
+    }
+    Some(placement)
+}
+
+/// The state following `start` once `mv` is played, or `None` if it can't be.
+pub fn after(start: &Start, mv: &Move) -> Option<Start> {
+    let mut next = start.clone();
+    let piece = mv.location.piece;
+    if next.queue.first() == Some(&piece) {
+        next.queue.remove(0);
+    } else if next.hold == Some(piece) && !next.queue.is_empty() {
+        next.hold = Some(next.queue.remove(0));
+    } else if next.hold.is_none() && next.queue.get(1) == Some(&piece) {
+        next.hold = Some(next.queue.remove(0));
+        next.queue.remove(0);
+    } else {
+        return None;
+    }
+
+    let columns = next.board.first().map_or(0, Vec::len);
+    for (x, y) in location_cases(&mv.location) {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        let case = next
+            .board
+            .get_mut(y as usize)
+            .and_then(|row| row.get_mut(x as usize))
+            .filter(|case| case.is_none())?;
+        *case = Some(piece.letter());
+    }
+    let rows = next.board.len();
+    next.board.retain(|row| row.iter().any(Option::is_none));
+    let lines = rows - next.board.len();
+    next.board.resize(rows, vec![None; columns]);
+    if lines > 0 {
+        next.combo += 1;
+        next.back_to_back = lines == 4 || mv.spin != Spin::None;
+    } else {
+        next.combo = 0;
+    }
+    Some(next)
+}
+
+fn send<W: Write>(output: &mut W, message: &impl Serialize) -> io::Result<()> {
+    let line = serde_json::to_string(message).map_err(io::Error::other)?;
+    writeln!(output, "{}", line)?;
+    output.flush()
+}
+
+/// An external bot engine, usually run as a child process.
+pub struct ExternalBot {
+    pub name: String,
+    pub version: String,
+    /// The process of the engine, which is waited for when the bot is dropped.
+    child: Option<Child>,
+    input: Box<dyn Write + Send>,
+    output: Receiver<io::Result<BotMessage>>,
+    /// Longest wait for an answer.
+    timeout: Duration,
+    /// Whether a game was started for the bot.
+    started: bool,
+    /// State the bot should be in, if it played the last suggestion given.
+    expected: Option<Start>,
+    /// Set once the bot failed, after which it doesn't play anymore.
+    failed: bool,
+}
+
+impl fmt::Debug for ExternalBot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ExternalBot")
+            .field("name", &self.name)
+            .field("version", &self.version)
+            .field("started", &self.started)
+            .field("failed", &self.failed)
+            .finish()
+    }
+}
+
+impl ExternalBot {
+    /// Runs `command`, a program followed by its arguments, and waits for it to be ready.
+    pub fn launch(command: &str) -> io::Result<ExternalBot> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| invalid_data("empty bot command".to_owned()))?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().expect("Bot input is piped");
+        let output = BufReader::new(child.stdout.take().expect("Bot output is piped"));
+        ExternalBot::connect(Some(child), Box::new(input), output, TIMEOUT)
+    }
+
+    /// Talks to an engine reading `input` and writing `output`, and waits for it to be ready.
+    /// Lines that aren't JSON are an error, messages this frontend doesn't know are ignored.
+    fn connect<R: BufRead + Send + 'static>(
+        child: Option<Child>,
+        input: Box<dyn Write + Send>,
+        output: R,
+        timeout: Duration,
+    ) -> io::Result<ExternalBot> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in output.lines() {
+                let message = line.and_then(|line| {
+                    serde_json::from_str::<serde_json::Value>(&line)
+                        .map_err(|_| invalid_data(format!("malformed message: {}", line)))
+                });
+                let message = match message {
+                    Ok(value) => match serde_json::from_value(value) {
+                        Ok(message) => Ok(message),
+                        Err(_) => continue,
+                    },
+                    Err(e) => Err(e),
+                };
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let mut bot = ExternalBot {
+            name: String::new(),
+            version: String::new(),
+            child,
+            input,
+            output: receiver,
+            timeout,
+            started: false,
+            expected: None,
+            failed: false,
+        };
+        loop {
+            if let BotMessage::Info { name, version, .. } = bot.receive()? {
+                bot.name = name;
+                bot.version = version;
+                break;
+            }
+        }
+        bot.send(&FrontendMessage::Rules {})?;
+        loop {
+            match bot.receive()? {
+                BotMessage::Ready {} => return Ok(bot),
+                BotMessage::Error { reason } => return Err(io::Error::other(reason)),
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&mut self, message: &FrontendMessage) -> io::Result<()> {
+        send(&mut self.input, message)
+    }
+
+    fn receive(&mut self) -> io::Result<BotMessage> {
+        match self.output.recv_timeout(self.timeout) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the bot took too long to answer",
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the bot exited",
+            )),
+        }
+    }
+
+    /// Asks the bot where to put the current piece of `game` and tells it the move is played.
+    /// Returns `None` once the bot failed, or if none of its moves can be played.
+    pub fn best_placement(&mut self, game: &Game) -> io::Result<Option<Placement>> {
+        if self.failed {
+            return Ok(None);
+        }
+        let placement = self.suggest(game);
+        self.failed = placement.is_err();
+        placement
+    }
+
+    fn suggest(&mut self, game: &Game) -> io::Result<Option<Placement>> {
+        let state = match start(game) {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+        self.sync(&state)?;
+        self.send(&FrontendMessage::Suggest {})?;
+        let moves = loop {
+            match self.receive()? {
+                BotMessage::Suggestion { moves } => break moves,
+                BotMessage::Error { reason } => return Err(io::Error::other(reason)),
+                _ => {}
+            }
+        };
+        for mv in moves {
+            if let Some(placement) = find_placement(game, &mv) {
+                self.send(&FrontendMessage::Play { mv })?;
+                self.expected = after(&state, &mv);
+                return Ok(Some(placement));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Brings the bot to `state`, with `new_piece` messages if it only misses the pieces that
+    /// entered the queue since its last move, or by starting over otherwise, e.g. after
+    /// garbage came in.
+    fn sync(&mut self, state: &Start) -> io::Result<()> {
+        if let Some(expected) = self.expected.take() {
+            if expected.board == state.board
+                && expected.hold == state.hold
+                && state.queue.starts_with(&expected.queue)
+            {
+                for piece in state.queue[expected.queue.len()..].iter() {
+                    self.send(&FrontendMessage::NewPiece { piece: *piece })?;
+                }
+                return Ok(());
+            }
+        }
+        if self.started {
+            self.send(&FrontendMessage::Stop {})?;
+        }
+        self.started = true;
+        self.send(&FrontendMessage::Start(state.clone()))
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit {});
+        let child = match self.child {
+            Some(ref mut child) => child,
+            None => return,
+        };
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Plays the bot side of the protocol with `bot` until told to quit, so that other frontends
+/// can use the bots of this game, and this frontend can be tested against a known bot.
+pub fn serve<R: BufRead, W: Write>(bot: &Bot, input: R, mut output: W) -> io::Result<()> {
+    send(
+        &mut output,
+        &BotMessage::Info {
+            name: env!("CARGO_PKG_NAME").to_owned(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            author: env!("CARGO_PKG_AUTHORS").to_owned(),
+            features: Vec::new(),
+        },
+    )?;
+    let mut state: Option<Start> = None;
+    for line in input.lines() {
+        // Messages this bot doesn't know are ignored.
+        let message = match serde_json::from_str(&line?) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        match message {
+            FrontendMessage::Rules {} => send(&mut output, &BotMessage::Ready {})?,
+            FrontendMessage::Start(start) => state = Some(start),
+            FrontendMessage::Stop {} => state = None,
+            FrontendMessage::Suggest {} => {
+                let moves = state.as_ref().and_then(|state| suggest(bot, state));
+                send(
+                    &mut output,
+                    &BotMessage::Suggestion {
+                        moves: moves.into_iter().collect(),
+                    },
+                )?;
+            }
+            FrontendMessage::Play { mv } => {
+                if let Some(next) = state.as_ref().and_then(|state| after(state, &mv)) {
+                    state = Some(next);
+                }
+            }
+            FrontendMessage::NewPiece { piece } => {
+                if let Some(ref mut state) = state {
+                    state.queue.push(piece);
+                }
+            }
+            FrontendMessage::Quit {} => break,
+        }
+    }
+    Ok(())
+}
+
+/// The move `bot` would play in `state`, on a board as high as the one of a new game.
+fn suggest(bot: &Bot, state: &Start) -> Option<Move> {
+    let rows = Game::with_seed(0).game_map.len();
+    let game = game(state, rows)?;
+    let placement = bot.best_placement(&game)?;
+    let piece = if placement.actions.first() == Some(&Action::Hold) {
+        game.hold.as_ref().or_else(|| game.queue.front())?
+    } else {
+        game.current_piece.as_ref()?
+    };
+    to_move(piece, &placement, rows)
+}
+
+/// Plays `pieces` pieces of a game with the bot run by `command`, pushing a garbage row every
+/// `garbage_interval` pieces so the bot has to start over now and then. Fails if the bot
+/// suggests a move that can't be played. Returns the game.
+pub fn self_test(command: &str, seed: u64, pieces: u32, garbage_interval: u32) -> io::Result<Game> {
+    let mut bot = ExternalBot::launch(command)?;
+    let mut game = Game::with_seed(seed);
+    for piece in 0..pieces {
+        if piece > 0
+            && piece % garbage_interval.max(1) == 0
+            && !game.insert_garbage_rows(1, SURVIVAL_HOLES)
+        {
+            break;
+        }
+        let current = game.create_new_tetromino();
+        if !current.test_current_position(&game.game_map) {
+            break;
+        }
+        game.current_piece = Some(current);
+        let placement = bot
+            .suggest(&game)?
+            .ok_or_else(|| invalid_data(format!("no playable move for piece {}", piece)))?;
+        for action in placement.actions {
+            game.apply_action(action);
+        }
+        if game.current_piece.is_some() {
+            return Err(invalid_data(format!("piece {} did not lock", piece)));
+        }
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::JoinHandle;
+
+    /// What the mock engine answers to `suggest`.
+    #[derive(Clone, Copy)]
+    enum Answer {
+        /// The first placement of the current piece the move generator finds.
+        FirstPlacement,
+        /// A line that isn't JSON.
+        Malformed,
+        Nothing,
+    }
+
+    /// Connects a bot to a mock engine run on a thread, which returns the messages it got once
+    /// the bot quits.
+    fn mock(answer: Answer, timeout: Duration) -> (ExternalBot, JoinHandle<Vec<FrontendMessage>>) {
+        let (engine_input, frontend_input) = io::pipe().unwrap();
+        let (frontend_output, mut engine_output) = io::pipe().unwrap();
+        let engine = thread::spawn(move || {
+            let info = BotMessage::Info {
+                name: "mock".to_owned(),
+                version: "1".to_owned(),
+                author: "tests".to_owned(),
+                features: Vec::new(),
+            };
+            send(&mut engine_output, &info).unwrap();
+            let mut received = Vec::new();
+            let mut state: Option<Start> = None;
+            for line in BufReader::new(engine_input).lines() {
+                let message: FrontendMessage = serde_json::from_str(&line.unwrap()).unwrap();
+                received.push(message.clone());
+                // The frontend may have stopped listening after a bad answer.
+                let _ = match message {
+                    FrontendMessage::Rules {} => send(&mut engine_output, &BotMessage::Ready {}),
+                    FrontendMessage::Start(start) => {
+                        state = Some(start);
+                        Ok(())
+                    }
+                    FrontendMessage::Play { mv } => {
+                        state = state.and_then(|state| after(&state, &mv));
+                        Ok(())
+                    }
+                    FrontendMessage::NewPiece { piece } => {
+                        if let Some(ref mut state) = state {
+                            state.queue.push(piece);
+                        }
+                        Ok(())
+                    }
+                    FrontendMessage::Suggest {} => match answer {
+                        Answer::FirstPlacement => {
+                            let moves = state.as_ref().and_then(first_move).into_iter().collect();
+                            send(&mut engine_output, &BotMessage::Suggestion { moves })
+                        }
+                        Answer::Malformed => writeln!(engine_output, "suggestion: T 4 0"),
+                        Answer::Nothing => Ok(()),
+                    },
+                    FrontendMessage::Quit {} => break,
+                    FrontendMessage::Stop {} => Ok(()),
+                };
+            }
+            received
+        });
+        let bot = ExternalBot::connect(
+            None,
+            Box::new(frontend_input),
+            BufReader::new(frontend_output),
+            timeout,
+        )
+        .unwrap();
+        (bot, engine)
+    }
+
+    fn first_move(state: &Start) -> Option<Move> {
+        let rows = Game::with_seed(0).game_map.len();
+        let game = game(state, rows)?;
+        let piece = game.current_piece.as_ref()?;
+        let placement = movegen::placements(&game.game_map, piece)
+            .into_iter()
+            .next()?;
+        to_move(piece, &placement, rows)
+    }
+
+    fn spawn(game: &mut Game) {
+        let piece = game.create_new_tetromino();
+        game.current_piece = Some(piece);
+    }
+
+    #[test]
+    fn plays_the_moves_of_a_mock_engine() {
+        let (mut bot, engine) = mock(Answer::FirstPlacement, TIMEOUT);
+        assert_eq!(bot.name, "mock");
+        let mut game = Game::with_seed(2);
+        for _ in 0..5 {
+            spawn(&mut game);
+            let placement = bot.best_placement(&game).unwrap().unwrap();
+            for action in placement.actions {
+                game.apply_action(action);
+            }
+            assert!(game.current_piece.is_none());
+        }
+        drop(bot);
+
+        let received = engine.join().unwrap();
+        let count = |matches: fn(&FrontendMessage) -> bool| {
+            received.iter().filter(|message| matches(message)).count()
+        };
+        assert_eq!(received[0], FrontendMessage::Rules {});
+        assert_eq!(count(|m| matches!(m, FrontendMessage::Start(_))), 1);
+        assert_eq!(count(|m| matches!(m, FrontendMessage::Suggest {})), 5);
+        assert_eq!(count(|m| matches!(m, FrontendMessage::Play { .. })), 5);
+        assert_eq!(count(|m| matches!(m, FrontendMessage::NewPiece { .. })), 4);
+        assert_eq!(received.last(), Some(&FrontendMessage::Quit {}));
+    }
+
+    #[test]
+    fn malformed_answers_fail_the_bot() {
+        let (mut bot, engine) = mock(Answer::Malformed, TIMEOUT);
+        let mut game = Game::with_seed(3);
+        spawn(&mut game);
+        let error = bot.best_placement(&game).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        // A failed bot doesn't play anymore.
+        assert_eq!(bot.best_placement(&game).unwrap(), None);
+        drop(bot);
+        engine.join().unwrap();
+    }
+
+    #[test]
+    fn silent_engines_time_out() {
+        let (mut bot, engine) = mock(Answer::Nothing, Duration::from_millis(200));
+        let mut game = Game::with_seed(4);
+        spawn(&mut game);
+        let error = bot.best_placement(&game).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        drop(bot);
+        engine.join().unwrap();
+    }
+}
@@ -5,6 +5,7 @@ pub trait TetrominoGenerator {
     fn new() -> Tetromino;
 }
 
+#[derive(Clone)]
 pub struct Tetromino {
     pub states: States,
     pub x: isize,
@@ -222,8 +223,33 @@ impl TetrominoGenerator for TetrominoT {
     }
 }
 
+/// Creates a piece in its spawn position from its kind, which is also its color: 1 for I, 2
+/// for J, 3 for L, 4 for O, 5 for S, 6 for Z and 7 for T.
+pub fn from_kind(kind: u8) -> Option<Tetromino> {
+    match kind {
+        1 => Some(TetrominoI::new()),
+        2 => Some(TetrominoJ::new()),
+        3 => Some(TetrominoL::new()),
+        4 => Some(TetrominoO::new()),
+        5 => Some(TetrominoS::new()),
+        6 => Some(TetrominoZ::new()),
+        7 => Some(TetrominoT::new()),
+        _ => None,
+    }
+}
+
 impl Tetromino {
-    pub fn rotate(&mut self, game_map: &[Vec<u8>]) {
+    /// See `from_kind`.
+    pub fn kind(&self) -> u8 {
+        self.states[0]
+            .iter()
+            .flat_map(|line| line.iter())
+            .cloned()
+            .find(|case| *case != 0)
+            .unwrap_or(0)
+    }
+
+    pub fn rotate(&mut self, game_map: &[Vec<u8>]) -> bool {
         let mut tmp_state = self.current_state + 1;
         if tmp_state as usize >= self.states.len() {
             tmp_state = 0;
@@ -233,9 +259,15 @@ impl Tetromino {
             if self.test_position(game_map, tmp_state as usize, self.x, self.y) == true {
                 self.current_state = tmp_state;
                 self.x += *x;
-                break;
+                return true;
             }
         }
+        false
+    }
+
+    /// Whether this is a T piece, the only one that can score T-spins.
+    pub fn is_t(&self) -> bool {
+        self.kind() == 7
     }
 
     pub fn change_position(&mut self, game_map: &[Vec<u8>], new_x: isize, new_y: usize) -> bool {
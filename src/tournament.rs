@@ -0,0 +1,214 @@
+//! Round-robin tournaments between bots in versus matches, rated with Elo.
+
+use std::io;
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::battle::Outcome;
+use crate::bot::{self, BotSpec};
+use crate::sim;
+
+/// Rating of every bot before its first match.
+pub const INITIAL_RATING: f64 = 1500.0;
+/// Largest rating change of a single match.
+pub const K_FACTOR: f64 = 16.0;
+
+/// How a match went.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MatchLog {
+    /// Indices of the bots playing the left and right boards.
+    pub bots: [usize; 2],
+    pub seed: u64,
+    /// Index of the bot that won, `None` for draws and matches still going at the frame limit.
+    pub winner: Option<usize>,
+    pub frames: u32,
+    pub lines: [u32; 2],
+    pub score: [u32; 2],
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Standing {
+    pub bot: String,
+    pub rating: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Every match of a round-robin: each pair of bots plays every seed twice, once on each side.
+/// Seeds come first so that every bot keeps playing until the end.
+pub fn schedule(bots: usize, seeds: Range<u64>) -> Vec<([usize; 2], u64)> {
+    let mut matches = Vec::new();
+    for seed in seeds {
+        for first in 0..bots {
+            for second in first + 1..bots {
+                matches.push(([first, second], seed));
+                matches.push(([second, first], seed));
+            }
+        }
+    }
+    matches
+}
+
+/// Plays a match between new bots built from the specs of `bots` in `specs`, the pieces of
+/// both coming from `seed`.
+pub fn play(
+    specs: &[BotSpec],
+    bots: [usize; 2],
+    delay: u32,
+    seed: u64,
+    max_frames: u32,
+) -> io::Result<MatchLog> {
+    let mut first = specs[bots[0]].build(delay)?;
+    let mut second = specs[bots[1]].build(delay)?;
+    let battle = bot::duel([&mut first, &mut second], seed, max_frames);
+    let games = [&battle.fighters[0].game, &battle.fighters[1].game];
+    Ok(MatchLog {
+        bots,
+        seed,
+        winner: match battle.outcome() {
+            Some(Outcome::Winner(side)) => Some(bots[side]),
+            Some(Outcome::Draw) | None => None,
+        },
+        frames: battle.frame,
+        lines: [games[0].num_lines, games[1].num_lines],
+        score: [games[0].score, games[1].score],
+    })
+}
+
+/// Plays the round-robin between `specs` on `seeds`, on `jobs` threads at once. Returns the
+/// logs in schedule order.
+pub fn run(
+    specs: &[BotSpec],
+    delay: u32,
+    seeds: Range<u64>,
+    max_frames: u32,
+    jobs: usize,
+) -> io::Result<Vec<MatchLog>> {
+    let matches = schedule(specs.len(), seeds);
+    sim::parallel(matches.len(), jobs, |index| {
+        let (bots, seed) = matches[index];
+        play(specs, bots, delay, seed, max_frames)
+    })
+}
+
+/// Ratings and results of `specs` after the matches of `logs`, best first. Ratings are updated
+/// after every match in order.
+pub fn standings(specs: &[BotSpec], logs: &[MatchLog]) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = specs
+        .iter()
+        .map(|spec| Standing {
+            bot: spec.to_string(),
+            rating: INITIAL_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+        })
+        .collect();
+    for log in logs {
+        let [first, second] = log.bots;
+        let result = match log.winner {
+            Some(winner) if winner == first => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+        let expected = expected_score(standings[first].rating, standings[second].rating);
+        let change = K_FACTOR * (result - expected);
+        standings[first].rating += change;
+        standings[second].rating -= change;
+        match log.winner {
+            Some(winner) => {
+                standings[winner].wins += 1;
+                standings[first + second - winner].losses += 1;
+            }
+            None => {
+                standings[first].draws += 1;
+                standings[second].draws += 1;
+            }
+        }
+    }
+    standings.sort_by(|a, b| {
+        b.rating
+            .partial_cmp(&a.rating)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    standings
+}
+
+/// Chances of a player rated `rating` against one rated `opponent`, a draw counting half.
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(bots: [usize; 2], winner: Option<usize>) -> MatchLog {
+        MatchLog {
+            bots,
+            seed: 0,
+            winner,
+            frames: 0,
+            lines: [0, 0],
+            score: [0, 0],
+        }
+    }
+
+    fn standing<'a>(standings: &'a [Standing], bot: &str) -> &'a Standing {
+        standings
+            .iter()
+            .find(|standing| standing.bot == bot)
+            .unwrap()
+    }
+
+    #[test]
+    fn equal_ratings_expect_a_draw() {
+        assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+        let favourite = expected_score(1700.0, 1500.0);
+        assert!(favourite > 0.75);
+        assert!((favourite + expected_score(1500.0, 1700.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn standings_credit_the_winner_on_either_side() {
+        let specs = [
+            BotSpec::Greedy,
+            "beam:2".parse().unwrap(),
+            "tbp:engine".parse().unwrap(),
+        ];
+        let logs = [
+            log([0, 1], Some(1)),
+            log([1, 0], Some(1)),
+            log([0, 2], None),
+            log([2, 1], Some(2)),
+        ];
+        let standings = standings(&specs, &logs);
+
+        let total: f64 = standings.iter().map(|standing| standing.rating).sum();
+        assert!((total - 3.0 * INITIAL_RATING).abs() < 1e-9);
+        assert!(standings
+            .windows(2)
+            .all(|pair| pair[0].rating >= pair[1].rating));
+
+        let greedy = standing(&standings, "greedy");
+        assert_eq!((greedy.wins, greedy.losses, greedy.draws), (0, 2, 1));
+        let beam = standing(&standings, "beam:2");
+        assert_eq!((beam.wins, beam.losses, beam.draws), (2, 1, 0));
+        let tbp = standing(&standings, "tbp:engine");
+        assert_eq!((tbp.wins, tbp.losses, tbp.draws), (1, 0, 1));
+    }
+
+    #[test]
+    fn a_draw_moves_ratings_towards_each_other() {
+        let specs = [BotSpec::Greedy, "beam:2".parse().unwrap()];
+        let logs = [log([0, 1], Some(0)), log([1, 0], None)];
+        let standings = standings(&specs, &logs);
+        let greedy = standing(&standings, "greedy").rating;
+        let beam = standing(&standings, "beam:2").rating;
+        // The win gives K / 2 to greedy, then the draw takes back part of it.
+        assert!(greedy > INITIAL_RATING && greedy < INITIAL_RATING + K_FACTOR / 2.0);
+        assert!((greedy + beam - 2.0 * INITIAL_RATING).abs() < 1e-9);
+    }
+}
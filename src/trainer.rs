@@ -0,0 +1,109 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::events::{AutoRepeat, KeyMap};
+use crate::options::Options;
+use crate::render::Renderer;
+use tetrs::board;
+use tetrs::finesse::Finesse;
+use tetrs::game::Game;
+
+/// Faulty orientations listed on the end screen, the full summary going to the standard output.
+const SUMMARY_SIZE: usize = 5;
+
+/// Trains finesse on random pieces without gravity, showing the faults as they happen and a
+/// summary by orientation once the player stops, until the player quits.
+pub fn run_finesse(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, options: &Options) {
+    loop {
+        let finesse = train(renderer, event_pump, options);
+        println!(
+            "Finesse: {}% of {} pieces, {} faults",
+            finesse.accuracy(),
+            finesse.pieces(),
+            finesse.faults()
+        );
+        for line in finesse.summary() {
+            println!("{}", line.to_lowercase());
+        }
+
+        let mut lines = vec![format!(
+            "FINESSE: {}% OF {} PIECES",
+            finesse.accuracy(),
+            finesse.pieces()
+        )];
+        let faulty = finesse
+            .tallies
+            .values()
+            .filter(|tally| tally.faults > 0)
+            .count();
+        lines.extend(finesse.summary().into_iter().take(faulty.min(SUMMARY_SIZE)));
+        lines.push("ENTER: AGAIN".to_owned());
+        lines.push("ESCAPE: QUIT".to_owned());
+        if !crate::display_end_screen(renderer, event_pump, &lines) {
+            return;
+        }
+    }
+}
+
+/// Plays until the player presses escape and returns the finesse of the pieces placed.
+/// Topping out clears the board and backspace starts over.
+fn train(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, options: &Options) -> Finesse {
+    let new_game = || match options.seed {
+        Some(seed) => Game::with_seed(seed),
+        None => Game::new(),
+    };
+    let mut game = new_game();
+    let mut finesse = Finesse::new(options.auto_shift);
+    let mut keys = AutoRepeat::new(KeyMap::single_player(), options.auto_shift);
+    spawn(&mut game, &mut finesse);
+    loop {
+        let mut quit = false;
+        let mut restart = false;
+        let mut export = false;
+        let inputs = keys.poll(event_pump, &mut quit, &mut restart, &mut export);
+        if quit {
+            return finesse;
+        }
+        if restart {
+            game = new_game();
+            finesse = Finesse::new(options.auto_shift);
+            spawn(&mut game, &mut finesse);
+        }
+        for input in inputs {
+            finesse.apply_input(&mut game, input);
+            spawn(&mut game, &mut finesse);
+        }
+        if export {
+            crate::print_fumens(&[&game]);
+        }
+        renderer.draw_boards(&[(&game, &info(&finesse))]);
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Spawns the next piece if the current one got locked, clearing the board if it doesn't fit.
+fn spawn(game: &mut Game, finesse: &mut Finesse) {
+    if game.current_piece.is_some() {
+        return;
+    }
+    let piece = game.create_new_tetromino();
+    if !piece.test_current_position(&game.game_map) {
+        game.clear_board();
+    }
+    game.current_piece = Some(piece);
+    finesse.spawned();
+}
+
+fn info(finesse: &Finesse) -> Vec<String> {
+    let mut texts = vec![
+        format!("PIECES: {}", finesse.pieces()),
+        format!("FAULTS: {}", finesse.faults()),
+        format!("FINESSE: {}%", finesse.accuracy()),
+    ];
+    if let Some(fault) = finesse.last_fault {
+        texts.push(format!("LAST FAULT: {}", board::letter(fault.kind)));
+        texts.push(format!("{} KEYS, {} NEEDED", fault.presses, fault.least));
+    }
+    texts.push("BACKSPACE: RESTART".to_owned());
+    texts
+}
@@ -0,0 +1,219 @@
+use sdl2::pixels::Color;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::battle::{Battle, Outcome};
+use crate::events::{self, KeyMap};
+use crate::net::{Connection, Lockstep, Session, Settings};
+use crate::options::Options;
+use crate::render::Renderer;
+use crate::rollback::{Rollback, TcpTransport};
+use tetrs::bot::Bot;
+use tetrs::spectate::Broadcaster;
+
+fn battle_seeds(seed: u64, same_pieces: bool) -> [u64; 2] {
+    if same_pieces {
+        [seed, seed]
+    } else {
+        [seed, seed.wrapping_add(1)]
+    }
+}
+
+/// Runs local versus matches until the players quit. Both players get the same pieces when
+/// `options.same_pieces` is set, and the bot plays player 2 when `options.bot` is.
+pub fn run_versus(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, options: &Options) {
+    let mut seed = options.seed.unwrap_or_else(rand::random);
+    let mut wins = [0, 0];
+    let mut broadcaster = crate::broadcaster(options);
+
+    loop {
+        let mut battle = Battle::new(
+            battle_seeds(seed, options.same_pieces),
+            options.garbage_delay,
+        );
+
+        let mut bot = crate::new_bot(options);
+        let outcome = match play_match(
+            renderer,
+            event_pump,
+            &mut broadcaster,
+            &mut bot,
+            &mut battle,
+        ) {
+            Some(outcome) => outcome,
+            None => return,
+        };
+        let title = match outcome {
+            Outcome::Winner(player) => {
+                wins[player] += 1;
+                format!("PLAYER {} WINS", player + 1)
+            }
+            Outcome::Draw => "DRAW".to_owned(),
+        };
+        println!("{}", title);
+        let lines = [
+            title,
+            format!("{} - {}", wins[0], wins[1]),
+            "ENTER: REMATCH".to_owned(),
+            "ESCAPE: QUIT".to_owned(),
+        ];
+        if !crate::display_end_screen(renderer, event_pump, &lines) {
+            return;
+        }
+        seed = rand::random();
+    }
+}
+
+/// Plays until a player tops out. Returns `None` if the players quit.
+fn play_match(
+    renderer: &mut Renderer,
+    event_pump: &mut sdl2::EventPump,
+    broadcaster: &mut Option<Broadcaster>,
+    bot: &mut Option<Bot>,
+    battle: &mut Battle,
+) -> Option<Outcome> {
+    // Against the bot, the player can use the single player keys.
+    let keymaps = if bot.is_some() {
+        vec![KeyMap::single_player()]
+    } else {
+        vec![KeyMap::player_one(), KeyMap::player_two()]
+    };
+
+    loop {
+        let mut quit = false;
+        let mut undo = false;
+        let mut export = false;
+        let mut actions =
+            events::poll_actions(event_pump, &keymaps, &mut quit, &mut undo, &mut export);
+        if quit {
+            return None;
+        }
+        if let Some(ref mut bot) = *bot {
+            actions.push(bot.next_actions(&battle.fighters[1].game));
+        }
+        battle.step([&actions[0], &actions[1]]);
+        if export {
+            crate::print_fumens(&[&battle.fighters[0].game, &battle.fighters[1].game]);
+        }
+
+        draw_battle(renderer, broadcaster, battle);
+        if let Some(outcome) = battle.outcome() {
+            return Some(outcome);
+        }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Plays a single match against a remote player, either hosting it (`options.host`), joining
+/// it (`options.connect`) or meeting the opponent in a room of a match server
+/// (`options.server`).
+pub fn run_network(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, options: &Options) {
+    renderer.clear(Color::RGB(0, 0, 0));
+    renderer.draw_centered_lines(&["WAITING FOR OPPONENT".to_owned()], 350, 50);
+    renderer.present();
+
+    let connection = match (&options.host, &options.connect) {
+        (Some(addr), _) => {
+            let seed = options.seed.unwrap_or_else(rand::random);
+            let settings = Settings {
+                seeds: battle_seeds(seed, options.same_pieces),
+                garbage_delay: options.garbage_delay,
+                input_delay: options.input_delay,
+            };
+            Connection::host(addr, settings).map(|connection| (connection, 0, settings))
+        }
+        (None, Some(addr)) => {
+            Connection::connect(addr).map(|(connection, settings)| (connection, 1, settings))
+        }
+        (None, None) => match options.server {
+            Some(ref addr) => Connection::join(addr, &options.room),
+            None => return,
+        },
+    };
+    let mut session: Box<dyn Session> = match connection {
+        Ok((connection, local, settings)) if options.rollback => Box::new(Rollback::new(
+            TcpTransport::new(connection),
+            local,
+            settings,
+        )),
+        Ok((connection, local, settings)) => Box::new(Lockstep::new(connection, local, settings)),
+        Err(e) => {
+            eprintln!("Connection failed: {}", e);
+            return;
+        }
+    };
+
+    let mut broadcaster = crate::broadcaster(options);
+    let mut bot = crate::new_bot(options);
+    let keymaps = [KeyMap::single_player()];
+    loop {
+        let mut quit = false;
+        let mut undo = false;
+        let mut export = false;
+        let mut actions =
+            events::poll_actions(event_pump, &keymaps, &mut quit, &mut undo, &mut export);
+        if quit {
+            session.quit();
+            return;
+        }
+        let local_actions = match bot {
+            Some(ref mut bot) => bot.next_actions(&session.battle().fighters[session.local()].game),
+            None => actions.remove(0),
+        };
+        match session.advance(local_actions) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("Opponent left");
+                return;
+            }
+            Err(e) => {
+                eprintln!("Connection lost: {}", e);
+                return;
+            }
+        }
+        if export {
+            let fighters = &session.battle().fighters;
+            crate::print_fumens(&[&fighters[0].game, &fighters[1].game]);
+        }
+
+        draw_battle(renderer, &mut broadcaster, session.battle());
+        if let Some(outcome) = session.outcome() {
+            let title = match outcome {
+                Outcome::Winner(player) if player == session.local() => "YOU WIN",
+                Outcome::Winner(_) => "YOU LOSE",
+                Outcome::Draw => "DRAW",
+            };
+            println!("{}", title);
+            crate::display_end_screen(
+                renderer,
+                event_pump,
+                &[title.to_owned(), "ESCAPE: QUIT".to_owned()],
+            );
+            return;
+        }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+/// Draws both boards and sends them to the spectators, if any.
+fn draw_battle(renderer: &mut Renderer, broadcaster: &mut Option<Broadcaster>, battle: &Battle) {
+    let texts: Vec<_> = battle
+        .fighters
+        .iter()
+        .map(|fighter| {
+            vec![
+                format!("SCORE: {}", fighter.game.score),
+                format!("LINES: {}", fighter.game.num_lines),
+                format!("INCOMING: {}", fighter.garbage.pending()),
+            ]
+        })
+        .collect();
+    let boards = [
+        (&battle.fighters[0].game, &texts[0][..]),
+        (&battle.fighters[1].game, &texts[1][..]),
+    ];
+    renderer.draw_boards(&boards);
+    if let Some(ref mut broadcaster) = *broadcaster {
+        broadcaster.publish(&boards);
+    }
+}
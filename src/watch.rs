@@ -0,0 +1,52 @@
+use sdl2::pixels::Color;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::events;
+use crate::render::Renderer;
+use tetrs::spectate::Spectator;
+
+/// Shows the boards of the game broadcast on `addr` until it ends or the spectator quits.
+pub fn run_watch(renderer: &mut Renderer, event_pump: &mut sdl2::EventPump, addr: &str) {
+    let mut spectator = match Spectator::connect(addr) {
+        Ok(spectator) => spectator,
+        Err(e) => {
+            eprintln!("Connection failed: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        let mut quit = false;
+        let mut undo = false;
+        let mut export = false;
+        events::poll_actions(event_pump, &[], &mut quit, &mut undo, &mut export);
+        if quit {
+            return;
+        }
+
+        match spectator.latest() {
+            Ok([]) => {
+                renderer.clear(Color::RGB(0, 0, 0));
+                renderer.draw_centered_lines(&["WAITING FOR THE GAME".to_owned()], 350, 50);
+                renderer.present();
+            }
+            Ok(boards) => {
+                if export {
+                    let games: Vec<_> = boards.iter().map(|board| &board.game).collect();
+                    crate::print_fumens(&games);
+                }
+                let boards: Vec<_> = boards
+                    .iter()
+                    .map(|board| (&board.game, &board.stats[..]))
+                    .collect();
+                renderer.draw_boards(&boards);
+            }
+            Err(e) => {
+                println!("Game over: {}", e);
+                return;
+            }
+        }
+        sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}